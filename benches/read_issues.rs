@@ -0,0 +1,144 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+//! Benchmarks for reading issues, over a synthetic repository of
+//! 50,000 issues (or EVICT_BENCH_ISSUES, if set).  Run with
+//! `cargo bench`.  The repository is generated in
+//! `target/bench-repo` the first time and reused afterwards.  They
+//! live outside the crate so that `cargo test` doesn't generate it.
+#![feature(test)]
+
+extern crate test;
+extern crate time;
+extern crate evict;
+
+use test::Bencher;
+
+use evict::file_manager;
+use evict::file_manager::ReadParts;
+use evict::file_util;
+use evict::issue::{Issue, IssueBase, IssueComment, IssueTag, IssueStatus};
+use evict::issue::IssueTimelineEvent::{TimelineComment, TimelineTag};
+use evict::issue_cache;
+use evict::author::Author;
+use evict::serdetime::SerdeTime;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+static BENCH_DIRECTORY:&'static str = "target/bench-repo";
+static DEFAULT_ISSUES:usize = 50000;
+static FIRST_ID:u64 = 1400000000000000000;
+
+fn issue_count() -> usize {
+  env::var("EVICT_BENCH_ISSUES").ok()
+     .and_then(|count| usize::from_str_radix(count.as_str(), 10).ok())
+     .unwrap_or(DEFAULT_ISSUES)
+}
+
+fn evict_dir() -> PathBuf {
+  Path::new(BENCH_DIRECTORY).join(file_manager::EVICT_DIRECTORY)
+}
+
+fn issue_dir() -> PathBuf {
+  evict_dir().join(file_manager::ISSUE_DIRECTORY)
+}
+
+///Creates the synthetic repository unless one of the right size
+///already exists.  Each issue has a tag and a comment.
+fn ensure_repository() -> PathBuf {
+  let count = issue_count();
+  let marker = Path::new(BENCH_DIRECTORY).join("issue-count");
+  let existing = file_util::read_string_from_path(marker.as_path()).unwrap_or("".to_string());
+  if existing.trim() != count.to_string() {
+    let _ = fs::remove_dir_all(BENCH_DIRECTORY);
+    fs::create_dir_all(issue_dir()).unwrap();
+    for index in 0 .. count {
+      write_synthetic_issue(index);
+    }
+    file_util::write_atomically(marker.as_path(), count.to_string().as_bytes()).unwrap();
+  }
+  issue_dir()
+}
+
+fn write_synthetic_issue(index:usize) {
+  let author = Author::new(format!("Author {}", index % 50),
+                           Some(format!("author{}@example.com", index % 50)));
+  //built directly, since Issue::new and IssueComment::new ask git for the branch
+  let id = (FIRST_ID + index as u64).to_string();
+  let now = SerdeTime(time::now());
+  let comment = IssueComment{creation_time:now, author:author.clone(),
+                             body_text:"A comment.".to_string(), branch:"master".to_string(),
+                             id:format!("{}1", id), copied_from:None, reply_to:None};
+  let issue = Issue{
+    base:IssueBase{title:format!("Synthetic issue {}", index),
+                   creation_time:now,
+                   author:author.clone(),
+                   id:id.clone(),
+                   branch:"master".to_string(),
+                   body_text:"Some text for the body of the issue.\n\nWith a second paragraph."
+                               .to_string()},
+    events:vec!(TimelineTag(IssueTag::new(format!("tag{}", index % 20), author, true)),
+                TimelineComment(comment)),
+    status:IssueStatus::default(),
+    reverse_links:vec!()
+  };
+
+  let dir = issue_dir().join(issue.id());
+  fs::create_dir_all(&dir).unwrap();
+  file_manager::write_json(dir.join("body").to_string_lossy().as_ref(),
+                           &issue.no_comment_json()).unwrap();
+  for event in issue.events.iter() {
+    file_manager::write_json(dir.join(event.id()).to_string_lossy().as_ref(), event).unwrap();
+  }
+}
+
+#[bench]
+pub fn read_all_parallel(b:&mut Bencher) {
+  let dir = ensure_repository();
+  b.iter(|| file_manager::read_issues_in(dir.as_path(), ReadParts::Everything));
+}
+
+#[bench]
+pub fn read_all_serial(b:&mut Bencher) {
+  let dir = ensure_repository();
+  b.iter(|| file_manager::read_issue_dirs_with_threads(file_manager::issue_dirs_in(dir.as_path()),
+                                                       ReadParts::Everything, 1));
+}
+
+#[bench]
+pub fn read_bodies_only(b:&mut Bencher) {
+  let dir = ensure_repository();
+  b.iter(|| file_manager::read_issues_in(dir.as_path(), ReadParts::BodyOnly));
+}
+
+#[bench]
+pub fn lazy_find_first(b:&mut Bencher) {
+  let dir = ensure_repository();
+  b.iter(|| file_manager::issue_reader_in(dir.as_path(), ReadParts::BodyOnly)
+                         .find(|issue| issue.title().starts_with("Synthetic")));
+}
+
+#[bench]
+pub fn cached_summaries(b:&mut Bencher) {
+  ensure_repository();
+  //the first read builds the cache, so the benchmark measures a warm cache
+  issue_cache::read_summaries_in(evict_dir().as_path());
+  b.iter(|| issue_cache::read_summaries_in(evict_dir().as_path()));
+}
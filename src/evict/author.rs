@@ -0,0 +1,215 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use file_util;
+use file_manager;
+use serde::{Deserialize, Deserializer};
+use serde::de::Error as DeError;
+use serde_json::value::Value as JsonValue;
+
+use std::fmt::{Display, Formatter};
+use std::fmt::Result as FmtResult;
+
+static MAILMAP_FILE:&'static str = "mailmap";
+
+///The identity of whoever created an issue, comment or tag.
+///Older versions of evict stored authors as free-form strings,
+///so deserialization accepts either a plain string (parsed
+///as `Name <email>`) or a `{name, email}` object.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+pub struct Author{
+  pub name:String,
+  pub email:Option<String>
+}
+
+impl Author{
+  pub fn new(name:String, email:Option<String>) -> Author {
+    Author{name:name, email:email}
+  }
+
+  ///Parses an author from the `Name <email>` form git uses.
+  ///If there is no `<email>` part the whole string is the name.
+  pub fn parse(text:&str) -> Author {
+    let trimmed = text.trim();
+    match (trimmed.find('<'), trimmed.ends_with('>')) {
+      (Some(start), true) => {
+        let email = trimmed[start + 1 .. trimmed.len() - 1].trim();
+        Author{name:trimmed[.. start].trim().to_string(),
+               email:if email.len() == 0 {None} else {Some(email.to_string())}}
+      }
+      _ => Author{name:trimmed.to_string(), email:None}
+    }
+  }
+
+  ///Checks whether this author is identified by `ident`, which
+  ///may be either the name or the email of the author.
+  pub fn matches(&self, ident:&str) -> bool {
+    let lowered = ident.to_lowercase();
+    self.name.to_lowercase() == lowered ||
+      self.email.as_ref().map_or(false, |email| email.to_lowercase() == lowered)
+  }
+}
+
+impl Display for Author {
+  fn fmt(&self, f:&mut Formatter) -> FmtResult {
+    match self.email {
+      Some(ref email) => write!(f, "{} <{}>", self.name, email),
+      None => write!(f, "{}", self.name)
+    }
+  }
+}
+
+impl Deserialize for Author {
+  fn deserialize<D>(deserializer: &mut D) -> Result<Author, D::Error>
+      where D: Deserializer {
+    match try!(JsonValue::deserialize(deserializer)) {
+      JsonValue::String(text) => Ok(Author::parse(text.as_str())),
+      JsonValue::Object(mut map) => {
+        let name = match map.remove("name") {
+          Some(JsonValue::String(name)) => name,
+          _ => return Err(D::Error::missing_field("name"))
+        };
+        let email = match map.remove("email") {
+          Some(JsonValue::String(email)) => Some(email),
+          _ => None
+        };
+        Ok(Author{name:name, email:email})
+      }
+      _ => Err(D::Error::invalid_value("author must be a string or an object"))
+    }
+  }
+}
+
+struct MailmapEntry{
+  proper_name:Option<String>,
+  proper_email:Option<String>,
+  commit_name:Option<String>,
+  commit_email:String
+}
+
+impl MailmapEntry{
+  fn applies_to(&self, author:&Author) -> bool {
+    let email_matches = match author.email {
+      Some(ref email) => email.to_lowercase() == self.commit_email.to_lowercase(),
+      None => self.commit_email.len() == 0
+    };
+    email_matches && self.commit_name.as_ref().map_or(true, |name| *name == author.name)
+  }
+}
+
+///Unifies author aliases using `.evict/mailmap`, which follows the
+///format of git's mailmap:
+///
+///    Proper Name <commit@email>
+///    <proper@email> <commit@email>
+///    Proper Name <proper@email> <commit@email>
+///    Proper Name <proper@email> Commit Name <commit@email>
+///
+///Authors recorded without an email can be matched with an empty
+///commit email, e.g. `Proper Name <proper@email> Old Name <>`.
+pub struct Mailmap{
+  entries:Vec<MailmapEntry>
+}
+
+impl Mailmap{
+  pub fn load() -> Mailmap {
+    let filename = format!("{}/{}", file_manager::EVICT_DIRECTORY, MAILMAP_FILE);
+    let contents = file_util::read_string_from_file(filename.as_str())
+                             .unwrap_or("".to_string());
+    Mailmap::parse(contents.as_str())
+  }
+
+  pub fn parse(contents:&str) -> Mailmap {
+    Mailmap{entries:contents.lines().filter_map(parse_mailmap_line).collect()}
+  }
+
+  ///Returns the canonical identity for an author, or a copy of the
+  ///author if no mailmap entry applies.  Later entries take priority
+  ///over earlier ones, as they do in git.
+  pub fn canonical(&self, author:&Author) -> Author {
+    match self.entries.iter().rev().find(|entry| entry.applies_to(author)) {
+      Some(entry) => Author{
+        name:entry.proper_name.clone().unwrap_or(author.name.clone()),
+        email:entry.proper_email.clone().or(author.email.clone())
+      },
+      None => author.clone()
+    }
+  }
+}
+
+fn parse_mailmap_line(line:&str) -> Option<MailmapEntry> {
+  let content = match line.find('#') {
+    Some(pos) => &line[.. pos],
+    None => line
+  };
+  //split into (name-before-email, email) pairs
+  let mut pairs:Vec<(Option<String>, String)> = vec!();
+  let mut rest = content;
+  while let (Some(start), Some(end)) = (rest.find('<'), rest.find('>')) {
+    if end < start {
+      return None;
+    }
+    let name = rest[.. start].trim();
+    pairs.push((if name.len() == 0 {None} else {Some(name.to_string())},
+                rest[start + 1 .. end].trim().to_string()));
+    rest = &rest[end + 1 ..];
+  }
+  match pairs.len() {
+    1 => {
+      let (name, email) = pairs.pop().unwrap();
+      name.map(|name| MailmapEntry{proper_name:Some(name), proper_email:None,
+                                   commit_name:None, commit_email:email})
+    }
+    2 => {
+      let (commit_name, commit_email) = pairs.pop().unwrap();
+      let (proper_name, proper_email) = pairs.pop().unwrap();
+      Some(MailmapEntry{proper_name:proper_name,
+                        proper_email:if proper_email.len() == 0 {None} else {Some(proper_email)},
+                        commit_name:commit_name,
+                        commit_email:commit_email})
+    }
+    _ => None
+  }
+}
+
+#[test]
+pub fn parse_author_string(){
+  let full = Author::parse("Jane Doe <jane@example.com>");
+  assert_eq!(full.name, "Jane Doe".to_string());
+  assert_eq!(full.email, Some("jane@example.com".to_string()));
+
+  let name_only = Author::parse("jdoe");
+  assert_eq!(name_only.name, "jdoe".to_string());
+  assert_eq!(name_only.email, None);
+  assert_eq!(format!("{}", full), "Jane Doe <jane@example.com>".to_string());
+}
+
+#[test]
+pub fn mailmap_unifies_aliases(){
+  let mailmap = Mailmap::parse("# comment line\n\
+                                Jane Doe <jane@example.com> <jd@old.example.com>\n\
+                                Jane Doe <jane@example.com> jdoe <>\n");
+  let by_email = Author::new("J. Doe".to_string(), Some("JD@old.example.com".to_string()));
+  let by_name = Author::parse("jdoe");
+  let unrelated = Author::parse("Someone <someone@example.com>");
+
+  let expected = Author::parse("Jane Doe <jane@example.com>");
+  assert_eq!(mailmap.canonical(&by_email), expected);
+  assert_eq!(mailmap.canonical(&by_name), expected);
+  assert_eq!(mailmap.canonical(&unrelated), unrelated);
+}
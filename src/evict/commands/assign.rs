@@ -0,0 +1,83 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use evict::issue::{IssueAssignment, Issue};
+use commands;
+use commands::bulk;
+use commands::ParsedArgs;
+use evict::author::Author;
+
+///Changes the assignee given with `create --assignee`.  Without
+///this an assignment could only be set when an issue is created.
+pub fn assign(args:&ParsedArgs) -> isize {
+  let bulkFlags = bulk::flags(args);
+  let ids = args.value("issue-id");
+  let assigneeArg = match args.value("assignee") {
+    Some(assignee) if ids.is_some() || bulkFlags.query.is_some() => assignee,
+    _ => {
+      println!("assign usage: evict assign <issue-id>[,<issue-id>...] <assignee>");
+      println!("              evict assign --where <query> [--yes] <assignee>");
+      println!("    Where <assignee> is `Name <email>`, or `me` for the current author.");
+      return 1;
+    }
+  };
+  let author = commands::get_author();
+  let assignee = if assigneeArg == "me" {
+    author.clone()
+  }else{
+    Author::parse(assigneeArg)
+  };
+  update_assignee(&bulkFlags, ids, Some(assignee), author)
+}
+
+pub fn unassign(args:&ParsedArgs) -> isize {
+  let bulkFlags = bulk::flags(args);
+  let ids = args.value("issue-id");
+  if ids.is_none() && bulkFlags.query.is_none() {
+    println!("unassign usage: evict unassign <issue-id>[,<issue-id>...]");
+    println!("                evict unassign --where <query> [--yes]");
+    return 1;
+  }
+  update_assignee(&bulkFlags, ids, None, commands::get_author())
+}
+
+fn update_assignee(bulkFlags:&bulk::BulkFlags, ids:Option<&str>, assignee:Option<Author>,
+                   author:Author) -> isize {
+  if bulk::is_bulk(bulkFlags, ids) {
+    let action = match assignee {
+      Some(ref assignee) => format!("Assign {} to", assignee),
+      None => "Unassign".to_string()
+    };
+    return bulk::update_issues(bulkFlags, ids, action.as_str(),
+                               |issue| modify_assignee(issue, assignee.clone(), author.clone()));
+  }
+  match commands::update_issue(ids.unwrap_or(""), |issue| modify_assignee(issue, assignee, author)) {
+    Ok(_) => 0,
+    Err(e) => {
+      println!("{}", e);
+      2
+    }
+  }
+}
+
+fn modify_assignee(mut issue:Issue, assignee:Option<Author>, author:Author) -> Issue {
+  if issue.assignee() != assignee.as_ref() {
+    issue.assign(IssueAssignment::new(assignee, author));
+  }
+  issue
+}
@@ -0,0 +1,132 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use evict::issue::Issue;
+use evict::query::Query;
+use evict::file_manager::IssueChange;
+use commands;
+use commands::{Arg, ParsedArgs};
+
+///The options shared by commands which can change several issues
+///at once: `--where <query>` and `--yes`.
+pub struct BulkFlags{
+  pub query:Option<String>,
+  pub yes:bool
+}
+
+///The `--where <query>` and `--yes` options given to a command.
+pub fn flags(args:&ParsedArgs) -> BulkFlags {
+  BulkFlags{query:args.value("--where").map(|q| q.to_string()), yes:args.has("--yes")}
+}
+
+///Whether the command may change more than one issue: it was
+///given a query or a comma separated list of IDs.
+pub fn is_bulk(flags:&BulkFlags, ids:Option<&str>) -> bool {
+  flags.query.is_some() || ids.map_or(false, |ids| ids.contains(','))
+}
+
+///Finds the issues matching each of the comma separated `ids`, or
+///every issue matching `--where` if no IDs are given.  Each ID must
+///match exactly one issue.  IDs are resolved through the summary
+///cache, so every issue is only read for a query without IDs.
+pub fn select_issues(flags:&BulkFlags, ids:Option<&str>) -> Result<Vec<Issue>, String> {
+  let repo = try!(commands::repository().map_err(|e| e.to_string()));
+  let mut selected:Vec<Issue> = vec!();
+  if let Some(ids) = ids {
+    for idPart in ids.split(',').map(|id| id.trim()).filter(|id| id.len() > 0) {
+      let issue = try!(commands::find_issue(&repo, idPart).map_err(|e| e.to_string()));
+      if !selected.contains(&issue) {
+        selected.push(issue);
+      }
+    }
+  }
+  if let Some(ref text) = flags.query {
+    let query = try!(Query::parse(text.as_str()));
+    let me = commands::find_author();
+    let matches = |issue:&Issue| query.matches(issue, me.as_ref());
+    if ids.is_some() {
+      selected.retain(|issue| matches(issue));
+    }else{
+      selected = repo.issues().into_iter().filter(|issue| matches(issue)).collect();
+    }
+  }
+  Ok(selected)
+}
+
+///Lists the issues about to be changed and asks whether to go
+///ahead, unless `--yes` was given.
+pub fn confirm(flags:&BulkFlags, action:&str, issues:&[Issue]) -> bool {
+  println!("{} {} issue{}:", action, issues.len(), if issues.len() == 1 {""} else {"s"});
+  for issue in issues.iter() {
+    println!("  {} {} [{}]", issue.id(), issue.title(), issue.status.name);
+  }
+  if flags.yes {
+    return true;
+  }
+  let answer = commands::prompt("Continue? [y/n] ");
+  answer.trim().to_lowercase().starts_with("y")
+}
+
+///Applies `update` to every issue and saves the changes together,
+///so they run one batch of hooks and are undone as one operation.
+pub fn apply_all<UF:Fn(Issue) -> Issue>(issues:Vec<Issue>, update:UF) -> isize {
+  let changes:Vec<IssueChange> = issues.into_iter().map(|issue| IssueChange::apply(issue, &update)).collect();
+  match commands::save_changes(changes.as_slice()) {
+    Ok(_) => 0,
+    Err(e) => {
+      println!("{}", e);
+      2
+    }
+  }
+}
+
+///Selects the issues for a bulk command, previews them and, once
+///confirmed, applies `update` to them all.  `action` describes the
+///change in the preview, e.g. `Tag sprint-3 on`.
+pub fn update_issues<UF:Fn(Issue) -> Issue>(flags:&BulkFlags, ids:Option<&str>, action:&str,
+                                            update:UF) -> isize {
+  let selected = match select_issues(flags, ids) {
+    Ok(selected) => selected,
+    Err(e) => {
+      println!("{}", e);
+      return 3;
+    }
+  };
+  run_confirmed(flags, action, selected, update)
+}
+
+///Previews `selected`, asks for confirmation and applies `update`.
+pub fn run_confirmed<UF:Fn(Issue) -> Issue>(flags:&BulkFlags, action:&str, selected:Vec<Issue>,
+                                            update:UF) -> isize {
+  if selected.len() == 0 {
+    println!("No issues matched");
+    return 3;
+  }
+  if !confirm(flags, action, selected.as_slice()) {
+    println!("Cancelled");
+    return 4;
+  }
+  apply_all(selected, update)
+}
+
+///The arguments taken by every command which can change several
+///issues at once.
+pub fn bulk_args() -> Vec<Arg> {
+  vec!(Arg::option("--where", "query", "Change every issue matching the query, e.g. 'tag:bug and status:open'"),
+       Arg::flag("--yes", "Don't ask before changing several issues"))
+}
@@ -18,9 +18,10 @@
  */
 use std;
 use commands;
-use file_manager;
+use commands::ParsedArgs;
+use evict::file_manager;
 
-pub fn clear_data(_:Vec<String>) -> isize {
+pub fn clear_data(_:&ParsedArgs) -> isize {
   let evictPath = std::path::Path::new(file_manager::EVICT_DIRECTORY);
   let absolute = evictPath.canonicalize();
   if absolute.is_err() {
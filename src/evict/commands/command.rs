@@ -0,0 +1,402 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::iter;
+
+///What the value of an argument can be completed to by the shell.
+///The dynamic kinds are listed by `evict __complete <kind>`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Completion{
+  Nothing,
+  Issue,
+  Status,
+  Tag,
+  Command,
+  File,
+  Values(&'static [&'static str])
+}
+
+impl Completion{
+  ///The kind given to `evict __complete`, for completions read from
+  ///the repository.
+  pub fn dynamic_kind(&self) -> Option<&'static str> {
+    match *self {
+      Completion::Issue => Some("issues"),
+      Completion::Status => Some("statuses"),
+      Completion::Tag => Some("tags"),
+      Completion::Command => Some("commands"),
+      _ => None
+    }
+  }
+}
+
+///One option or positional argument which a command takes.
+///Options start with `-`; anything else is a positional argument,
+///named for help output.
+#[derive(Clone, Copy, Debug)]
+pub struct Arg{
+  pub name:&'static str,
+  ///Another way of writing the option, such as `-m` for `--message`.
+  pub alias:Option<&'static str>,
+  ///The name of the option's value.  Flags have none.
+  pub value:Option<&'static str>,
+  pub required:bool,
+  ///Set on a final positional argument which takes everything left.
+  pub rest:bool,
+  pub help:&'static str,
+  pub completion:Completion
+}
+
+impl Arg{
+  ///An option with no value, such as `--raw`.
+  pub fn flag(name:&'static str, help:&'static str) -> Arg {
+    Arg{name:name, alias:None, value:None, required:false, rest:false, help:help,
+        completion:Completion::Nothing}
+  }
+
+  ///An option followed by a value, such as `--tag <tag>`.
+  pub fn option(name:&'static str, value:&'static str, help:&'static str) -> Arg {
+    Arg{value:Some(value), .. Arg::flag(name, help)}
+  }
+
+  ///A required positional argument.
+  pub fn positional(name:&'static str, help:&'static str) -> Arg {
+    Arg{required:true, .. Arg::flag(name, help)}
+  }
+
+  ///Any number of trailing positional arguments.
+  pub fn rest(name:&'static str, help:&'static str) -> Arg {
+    Arg{rest:true, .. Arg::flag(name, help)}
+  }
+
+  pub fn alias(self, alias:&'static str) -> Arg {
+    Arg{alias:Some(alias), .. self}
+  }
+
+  pub fn optional(self) -> Arg {
+    Arg{required:false, .. self}
+  }
+
+  pub fn required(self) -> Arg {
+    Arg{required:true, .. self}
+  }
+
+  pub fn completes(self, completion:Completion) -> Arg {
+    Arg{completion:completion, .. self}
+  }
+
+  pub fn is_option(&self) -> bool {
+    self.name.starts_with("-")
+  }
+
+  fn matches(&self, given:&str) -> bool {
+    self.name == given || self.alias == Some(given)
+  }
+
+  ///How the argument is written in a usage line.
+  pub fn usage(&self) -> String {
+    let inner = match (self.is_option(), self.value) {
+      (true, Some(value)) => format!("{} <{}>", self.name, value),
+      (true, None) => self.name.to_string(),
+      (false, _) if self.rest => format!("{}...", self.name),
+      (false, _) => format!("<{}>", self.name)
+    };
+    if self.required {inner} else {format!("[{}]", inner)}
+  }
+}
+
+///An evict subcommand.  Commands declare their arguments, which are
+///checked before the command is run and used for `evict help`.
+pub trait Command {
+  fn name(&self) -> &str;
+  ///One line describing the command, for `evict help`.
+  fn summary(&self) -> &str;
+  fn args(&self) -> &[Arg];
+  ///Whether the command needs an evict directory to work in.
+  fn needs_repository(&self) -> bool;
+  ///Whether the command holds the repository lock while it runs.
+  ///Changes to issues take the lock as they are saved, so only
+  ///commands which change statuses, milestones, config or undo need it.
+  fn takes_lock(&self) -> bool;
+  ///Hidden commands are left out of `evict help` and completions.
+  fn hidden(&self) -> bool;
+  fn run(&self, args:&ParsedArgs) -> isize;
+}
+
+/* Most commands are a function taking the arguments parsed from
+ * the ones they declare, which performs some action and returns an
+ * exit code.
+ */
+pub type CommandFn = fn (&ParsedArgs) -> isize;
+
+///A Command made of a CommandFn and a description of its arguments.
+pub struct StandardCommand{
+  name:&'static str,
+  summary:&'static str,
+  args:Vec<Arg>,
+  needsRepository:bool,
+  takesLock:bool,
+  hidden:bool,
+  run:CommandFn
+}
+
+impl StandardCommand{
+  pub fn new(name:&'static str, summary:&'static str, run:CommandFn) -> StandardCommand {
+    StandardCommand{name:name, summary:summary, args:vec!(), needsRepository:true,
+                    takesLock:false, hidden:false, run:run}
+  }
+
+  pub fn arg(mut self, arg:Arg) -> StandardCommand {
+    self.args.push(arg);
+    self
+  }
+
+  pub fn args(mut self, args:Vec<Arg>) -> StandardCommand {
+    self.args.extend(args.into_iter());
+    self
+  }
+
+  ///Marks the command as one which holds the lock while it runs,
+  ///because it writes more than issues.
+  pub fn with_lock(self) -> StandardCommand {
+    StandardCommand{takesLock:true, .. self}
+  }
+
+  ///Marks the command as one for scripts rather than people.
+  pub fn hide(self) -> StandardCommand {
+    StandardCommand{hidden:true, .. self}
+  }
+
+  ///Marks the command as one which runs without an evict directory.
+  pub fn without_repository(self) -> StandardCommand {
+    StandardCommand{needsRepository:false, takesLock:false, .. self}
+  }
+}
+
+impl Command for StandardCommand {
+  fn name(&self) -> &str {self.name}
+  fn summary(&self) -> &str {self.summary}
+  fn args(&self) -> &[Arg] {self.args.as_slice()}
+  fn needs_repository(&self) -> bool {self.needsRepository}
+  fn takes_lock(&self) -> bool {self.takesLock}
+  fn hidden(&self) -> bool {self.hidden}
+  fn run(&self, args:&ParsedArgs) -> isize {(self.run)(args)}
+}
+
+///The arguments given to a command, by the names it declared them
+///with.  Options are found by their full name even if an alias was
+///given, and positional arguments by the name used in help output.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ParsedArgs{
+  given:Vec<(&'static str, Option<String>)>
+}
+
+impl ParsedArgs{
+  ///Whether the option or positional argument `name` was given.
+  pub fn has(&self, name:&str) -> bool {
+    self.given.iter().any(|&(given, _)| given == name)
+  }
+
+  ///The value of a required positional argument, which parse_args
+  ///has already checked was given.
+  pub fn arg(&self, name:&str) -> &str {
+    self.value(name).unwrap_or("")
+  }
+
+  ///The value of `name`.  If an option was repeated, this is the
+  ///last value given.
+  pub fn value(&self, name:&str) -> Option<&str> {
+    self.values(name).pop()
+  }
+
+  ///Every value of `name`, in the order given, for options which may
+  ///be repeated and for a final positional argument which takes the
+  ///rest.
+  pub fn values(&self, name:&str) -> Vec<&str> {
+    self.given.iter()
+              .filter(|&&(given, _)| given == name)
+              .filter_map(|&(_, ref value)| value.as_ref().map(|v| v.as_str()))
+              .collect()
+  }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum ArgCheck{
+  Run(ParsedArgs),
+  ///`--help` or `-h` was given in place of an option.
+  Help
+}
+
+///Checks `given` against the arguments `declared` by a command and
+///sorts them out by name: every option must be known, have its value
+///and appear if it's required, and there must be the right number of
+///positional arguments.  When optional positional arguments are left
+///out, the required ones are filled first and the optional ones from
+///the left, so `[issue-id] <tag>` given one argument takes it as the tag.
+pub fn parse_args(declared:&[Arg], given:&[String]) -> Result<ArgCheck, String> {
+  let mut parsed = ParsedArgs::default();
+  let mut positionals:Vec<String> = vec!();
+  let mut iter = given.iter();
+  while let Some(arg) = iter.next() {
+    let text = arg.as_str();
+    //`-` alone stands for stdin, so is a value rather than an option
+    if !text.starts_with("-") || text == "-" {
+      positionals.push(arg.clone());
+      continue;
+    }
+    if text == "--help" || text == "-h" {
+      return Ok(ArgCheck::Help);
+    }
+    let option = match declared.iter().find(|a| a.is_option() && a.matches(text)) {
+      Some(option) => option,
+      None => return Err(format!("Unknown option {}", text))
+    };
+    let value = match option.value {
+      Some(valueName) => match iter.next() {
+        Some(value) => Some(value.clone()),
+        None => return Err(format!("{} needs a value: {} <{}>", text, option.name, valueName))
+      },
+      None => None
+    };
+    parsed.given.push((option.name, value));
+  }
+  for option in declared.iter().filter(|a| a.is_option() && a.required) {
+    if !parsed.has(option.name) {
+      return Err(format!("Missing required option {}", option.usage()));
+    }
+  }
+  let named:Vec<&Arg> = declared.iter().filter(|a| !a.is_option()).collect();
+  let required = named.iter().filter(|a| a.required).count();
+  let takesRest = named.iter().any(|a| a.rest);
+  if positionals.len() < required {
+    //optional arguments may come first, so name the required one which is missing
+    if let Some(missing) = named.iter().filter(|a| a.required).nth(positionals.len()) {
+      return Err(format!("Missing argument {}", missing.usage()));
+    }
+  }
+  if positionals.len() > named.len() && !takesRest {
+    return Err(format!("Too many arguments: expected at most {}, got {}",
+                       named.len(), positionals.len()));
+  }
+  let mut optionalLeft = positionals.len().saturating_sub(required);
+  let mut values = positionals.into_iter();
+  for arg in named.iter() {
+    if arg.rest {
+      for value in values.by_ref() {
+        parsed.given.push((arg.name, Some(value)));
+      }
+    }else if arg.required || optionalLeft > 0 {
+      if !arg.required {
+        optionalLeft -= 1;
+      }
+      if let Some(value) = values.next() {
+        parsed.given.push((arg.name, Some(value)));
+      }
+    }
+  }
+  Ok(ArgCheck::Run(parsed))
+}
+
+///The usage line for a command, e.g. `evict tag <issue-id> <tag>`.
+pub fn usage_line(command:&Command) -> String {
+  let args = command.args();
+  let positional = args.iter().filter(|a| !a.is_option());
+  let options = args.iter().filter(|a| a.is_option());
+  let parts:Vec<String> = iter::once(format!("evict {}", command.name()))
+                              .chain(positional.chain(options).map(|a| a.usage()))
+                              .collect();
+  parts.join(" ")
+}
+
+///Prints the usage, summary and arguments of a command.
+pub fn print_help(command:&Command) {
+  println!("usage: {}", usage_line(command));
+  println!("");
+  println!("{}", command.summary());
+  let args = command.args();
+  if args.len() == 0 {
+    return;
+  }
+  let labels:Vec<String> = args.iter().map(|a| {
+    let name = match a.alias {
+      Some(alias) => format!("{}, {}", alias, a.name),
+      None => a.name.to_string()
+    };
+    match a.value {
+      Some(value) => format!("{} <{}>", name, value),
+      None => name
+    }
+  }).collect();
+  let width = labels.iter().map(|l| l.len()).max().unwrap_or(0);
+  println!("");
+  for (arg, label) in args.iter().zip(labels.iter()) {
+    let padding:String = iter::repeat(' ').take(width - label.len()).collect();
+    println!("    {}{}  {}", label, padding, arg.help);
+  }
+}
+
+#[test]
+pub fn parse_args_validates_options_and_positionals(){
+  let declared = [Arg::positional("issue-id", ""),
+                  Arg::option("--tag", "tag", ""),
+                  Arg::flag("--raw", "").alias("-r")];
+  let args = |list:&[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+  let parse = |list:&[&str]| parse_args(&declared, args(list).as_slice());
+  match parse(&["abcd", "--tag", "x", "-r", "--tag", "y"]) {
+    Ok(ArgCheck::Run(parsed)) => {
+      assert_eq!(parsed.value("issue-id"), Some("abcd"));
+      assert_eq!(parsed.values("--tag"), vec!("x", "y"));
+      assert_eq!(parsed.value("--tag"), Some("y"));
+      assert!(parsed.has("--raw"));
+    }
+    other => panic!("expected to run, got {:?}", other)
+  }
+  assert_eq!(parse(&["abcd", "--help"]), Ok(ArgCheck::Help));
+  //a value which looks like an option is still a value
+  match parse(&["abcd", "--tag", "--raw"]) {
+    Ok(ArgCheck::Run(parsed)) => {
+      assert_eq!(parsed.value("--tag"), Some("--raw"));
+      assert!(!parsed.has("--raw"));
+    }
+    other => panic!("expected to run, got {:?}", other)
+  }
+  assert!(parse(&["abcd", "--bogus"]).is_err());
+  assert!(parse(&["abcd", "--tag"]).is_err());
+  assert!(parse(&["--raw"]).is_err());
+  assert!(parse(&["abcd", "efgh"]).is_err());
+}
+
+#[test]
+pub fn parse_args_fills_required_positionals_first(){
+  let declared = [Arg::positional("issue-id", "").optional(),
+                  Arg::positional("tag", ""),
+                  Arg::option("--to", "status", ""),
+                  Arg::rest("args", "")];
+  let args = |list:&[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+  let parse = |list:&[&str]| match parse_args(&declared, args(list).as_slice()) {
+    Ok(ArgCheck::Run(parsed)) => parsed,
+    other => panic!("expected to run, got {:?}", other)
+  };
+  let one = parse(&["bug"]);
+  assert_eq!((one.value("issue-id"), one.value("tag")), (None, Some("bug")));
+  //options can come anywhere, not only after the positional arguments
+  let all = parse(&["--to", "closed", "abcd", "bug", "x", "y"]);
+  assert_eq!((all.value("issue-id"), all.value("tag")), (Some("abcd"), Some("bug")));
+  assert_eq!(all.value("--to"), Some("closed"));
+  assert_eq!(all.values("args"), vec!("x", "y"));
+}
@@ -16,73 +16,251 @@
  *   You should have received a copy of the GNU General Public License
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
-use fsm;
-use issue::{Issue,IssueComment};
-use issue::IssueTimelineEvent::{TimelineComment};
-use file_manager;
-use file_util;
+use evict::issue::{Issue, IssueComment, IssueCommentEdit};
+use evict::issue::IssueTimelineEvent::{TimelineComment};
+use evict::file_manager::IssueChange;
+use evict::file_util;
+use evict::author::Author;
 use commands;
-use selection;
+use commands::bulk;
+use commands::ParsedArgs;
 
+static EDITOR_FILE_NAME:&'static str = "COMMENT";
 
 #[derive(Clone)]
 struct Flags{
-  issueIdPart:Option<String>
+  issueIdPart:Option<String>,
+  message:Option<String>,
+  file:Option<String>,
+  replyTo:Option<String>,
+  edit:Option<String>,
+  delete:Option<String>
 }
 
-fn std_handler(flags:Flags, arg:String) -> fsm::NextState<Flags, String> {
-  match arg {
-    idPart => fsm::NextState::Continue(Flags{issueIdPart:Some(idPart), .. flags})
+impl Flags{
+  fn from_args(args:&ParsedArgs) -> Flags {
+    let value = |name:&str| args.value(name).map(|v| v.to_string());
+    Flags{issueIdPart:value("issue-id"),
+          message:value("--message"),
+          file:value("--file"),
+          replyTo:value("--reply-to"),
+          edit:value("--edit"),
+          delete:value("--delete")}
   }
 }
 
-pub fn new_comment(args:Vec<String>) -> isize{
-  let mut stateMachine = fsm::StateMachine::new(std_handler, Flags{issueIdPart:None});
-  for a in args.into_iter(){
-    stateMachine.process(a);
-  }
-
-  let finalFlags = stateMachine.extract_state();
-  if finalFlags.issueIdPart.is_none() {
+pub fn new_comment(args:&ParsedArgs) -> isize{
+  let bulkFlags = bulk::flags(args);
+  let finalFlags = Flags::from_args(args);
+  if let Some(ref commentId) = finalFlags.delete {
+    change_comment(commentId.as_str(), |_| Some(None))
+  }else if let Some(ref commentId) = finalFlags.edit {
+    change_comment(commentId.as_str(), |current| read_comment_body(&finalFlags, current).map(Some))
+  }else if bulk::is_bulk(&bulkFlags, finalFlags.issueIdPart.as_ref().map(|ids| ids.as_str())) {
+    comment_bulk(&bulkFlags, finalFlags.clone())
+  }else if finalFlags.issueIdPart.is_none() {
     println!("The id for the issue, or an end section of it must be provided.");
     1
   }else{
-    let issues = file_manager::read_issues();
+    comment_on_single(finalFlags.issueIdPart.as_ref().unwrap().as_str(), &finalFlags)
+  }
+}
 
-    let updated = selection::update_issue(finalFlags.issueIdPart.unwrap().as_str(), 
-                                          issues,
-                                          comment_on_matching);
-    match file_manager::write_issues(updated.as_slice()) {
-      Ok(_) => 0,
-      Err(e) => {
-        println!("{}", e);
-        1
-      }
+///Comments on the issue matching `idPart`.  The reply target and body
+///are checked before anything is saved, so a bad --reply-to or an
+///empty body fails the command rather than saving nothing.
+fn comment_on_single(idPart:&str, flags:&Flags) -> isize {
+  let repo = match commands::repository() {
+    Ok(repo) => repo,
+    Err(e) => {
+      println!("{}", e);
+      return 1;
+    }
+  };
+  let issue = match commands::find_issue(&repo, idPart) {
+    Ok(issue) => issue,
+    Err(e) => {
+      println!("{}", e);
+      return 1;
+    }
+  };
+  let comment = match comment_for(&issue, flags, commands::get_author) {
+    Ok(comment) => comment,
+    Err(code) => return code
+  };
+  let change = IssueChange::apply(issue, |mut issue| {
+    issue.events.push(TimelineComment(comment));
+    issue
+  });
+  match commands::save(&repo, &[change]) {
+    Ok(_) => 0,
+    Err(e) => {
+      println!("{}", e);
+      1
     }
   }
 }
 
-fn comment_on_matching(matching:Issue) -> Issue {
+///Adds the same comment to every selected issue.  The body is read
+///once, before the preview, rather than once per issue.
+fn comment_bulk(bulkFlags:&bulk::BulkFlags, flags:Flags) -> isize {
+  if flags.replyTo.is_some() {
+    println!("--reply-to can only be used on a single issue");
+    return 1;
+  }
+  let selected = match bulk::select_issues(bulkFlags, flags.issueIdPart.as_ref().map(|ids| ids.as_str())) {
+    Ok(selected) => selected,
+    Err(e) => {
+      println!("{}", e);
+      return 3;
+    }
+  };
+  //checked here as well as in run_confirmed, so the editor isn't opened for nothing
+  if selected.len() == 0 {
+    println!("No issues matched");
+    return 3;
+  }
+  let body = match read_comment_body(&flags, "") {
+    Some(body) => body,
+    None => return 2
+  };
   let author = commands::get_author();
-  let filename = format!("COMMENT_ON_{}",matching.id());
-  let edited = commands::edit_file(filename.as_str());
-  if !edited {
-    println!("No comment body provided");
-    matching 
+  bulk::run_confirmed(bulkFlags, "Comment on", selected, |mut issue| {
+    issue.events.push(TimelineComment(IssueComment::new(author.clone(), body.clone())));
+    issue
+  })
+}
+
+///Reads a comment body from -m, --file (where `-` is stdin) or
+///the editor, which starts out containing `initial`.
+fn read_comment_body(flags:&Flags, initial:&str) -> Option<String> {
+  let text = match (flags.message.as_ref(), flags.file.as_ref()) {
+    (Some(message), _) => Some(message.clone()),
+    (None, Some(file)) if file.as_str() == "-" => commands::read_stdin(),
+    (None, Some(file)) => file_util::read_string_from_file(file.as_str()).ok(),
+    (None, None) => commands::edit_text(EDITOR_FILE_NAME, initial)
+  };
+  match text {
+    Some(ref body) if body.trim().len() == 0 => {
+      println!("No comment body provided");
+      None
+    }
+    None => {
+      println!("Could not read comment body");
+      None
+    }
+    body => body
+  }
+}
+
+///The comment to add to `issue`, or the exit code to fail with if
+///--reply-to doesn't name a single comment on it (3) or there is no
+///body (2), the same code comment_bulk uses.  The reply target is checked first, so the editor isn't
+///opened for a comment which can't be added.
+fn comment_for<A:FnOnce() -> Author>(issue:&Issue, flags:&Flags, author:A) -> Result<IssueComment, isize> {
+  let replyTo = match flags.replyTo {
+    Some(ref idPart) => match single_comment(issue, idPart.as_str()) {
+      Some(id) => Some(id),
+      None => return Err(3)
+    },
+    None => None
+  };
+  let text = match read_comment_body(flags, "") {
+    Some(text) => text,
+    None => return Err(2)
+  };
+  Ok(match replyTo {
+    Some(id) => IssueComment::reply(author(), text, id),
+    None => IssueComment::new(author(), text)
+  })
+}
+
+///Returns the ID of the single comment on `issue` whose ID ends
+///with `idPart`, printing the candidates if there isn't exactly one.
+fn single_comment(issue:&Issue, idPart:&str) -> Option<String> {
+  let comments = issue.find_comments(idPart);
+  if comments.len() != 1 {
+    println!("Found 0 or >1 matching comments:");
+    for comment in comments.iter() {
+      println!("{} by {}", comment.id, comment.author);
+    }
+    None
   }else{
-    let text = file_util::read_string_from_file(filename.as_str());
-    file_util::delete_file(filename.as_str());
-    if text.is_err() {
-      println!("Could not read comment body from file");
-      matching
-    }else{
-      let newComment = TimelineComment(IssueComment::new(author, text.unwrap()));
-      let mut newEvents = matching.events.clone();
-      newEvents.push(newComment);
-      let newIssue = Issue{events:newEvents,
-                            .. matching};
-      newIssue
+    Some(comments[0].id.clone())
+  }
+}
+
+///Edits or deletes the comment whose ID ends with `idPart`, on
+///whichever issue it is on.  `change` is given the current text
+///and returns the new text, None to delete, or nothing to abort.
+fn change_comment<F>(idPart:&str, change:F) -> isize
+    where F:Fn(&str) -> Option<Option<String>> {
+  let issues = match commands::repository() {
+    Ok(repo) => repo.issues(),
+    Err(e) => {
+      println!("{}", e);
+      return 1;
+    }
+  };
+  let found:Vec<(usize, IssueComment)> = issues.iter().enumerate().flat_map(|(index, issue)| {
+    issue.find_comments(idPart).into_iter()
+         .filter(|comment| issue.comment_text(comment).is_some())
+         .map(|comment| (index, comment.clone()))
+         .collect::<Vec<(usize, IssueComment)>>()
+  }).collect();
+  if found.len() != 1 {
+    println!("Found 0 or >1 matching comments:");
+    for &(index, ref comment) in found.iter() {
+      println!("{} on issue {}", comment.id, issues[index].id());
+    }
+    return 1;
+  }
+  let (index, ref comment) = found[0];
+  let newText = {
+    let current = issues[index].comment_text(comment).unwrap_or("");
+    match change(current) {
+      Some(text) => text,
+      None => return 2
+    }
+  };
+  let change = IssueChange::apply(issues[index].clone(), |mut issue| {
+    issue.edit_comment(IssueCommentEdit::new(comment.id.clone(), newText,
+                                             commands::get_author()));
+    issue
+  });
+  match commands::save_changes(&[change]) {
+    Ok(_) => 0,
+    Err(e) => {
+      println!("{}", e);
+      3
     }
   }
 }
 
+#[cfg(test)]
+fn message_flags(message:&str, replyTo:Option<&str>) -> Flags {
+  Flags{issueIdPart:None, message:Some(message.to_string()), file:None,
+        replyTo:replyTo.map(|id| id.to_string()), edit:None, delete:None}
+}
+
+#[test]
+pub fn comment_needs_a_body_and_one_reply_target(){
+  let mut issue = Issue::new("A".to_string(), "B".to_string(), Author::parse("C"));
+  let mut first = IssueComment::new(Author::parse("C"), "first".to_string());
+  first.id = "1111aaaa".to_string();
+  let mut second = IssueComment::new(Author::parse("C"), "second".to_string());
+  second.id = "2222aaaa".to_string();
+  issue.events.push(TimelineComment(first));
+  issue.events.push(TimelineComment(second));
+
+  assert_eq!(comment_for(&issue, &message_flags("  ", None), || Author::parse("C")).err(), Some(2));
+  assert_eq!(comment_for(&issue, &message_flags("hi", Some("aaaa")), || Author::parse("C")).err(), Some(3));
+  assert_eq!(comment_for(&issue, &message_flags("hi", Some("3333")), || Author::parse("C")).err(), Some(3));
+  match comment_for(&issue, &message_flags("hi", Some("1111aaaa")), || Author::parse("C")) {
+    Ok(comment) => {
+      assert_eq!(comment.reply_to, Some("1111aaaa".to_string()));
+      assert_eq!(comment.body_text, "hi".to_string());
+    }
+    Err(code) => panic!("expected a reply, got exit code {}", code)
+  }
+}
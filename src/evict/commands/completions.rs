@@ -0,0 +1,396 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use commands;
+use commands::{Command, CommandList, Arg, Completion, ParsedArgs};
+use evict::repository::{Repository, RepositoryError};
+use evict::issue_cache::IssueSummary;
+use evict::status_storage;
+
+use std::collections::BTreeMap;
+use std::env;
+
+pub static SHELLS:[&'static str; 3] = ["bash", "zsh", "fish"];
+
+pub fn completions(args:&ParsedArgs) -> isize {
+  let commandList = commands::standard_commands();
+  let cmds = visible_commands(&commandList);
+  let script = match args.arg("shell") {
+    "bash" => bash_script(cmds.as_slice()),
+    "zsh" => zsh_script(cmds.as_slice()),
+    "fish" => fish_script(cmds.as_slice()),
+    other => {
+      println!("Can't make completions for {}.  Shells are: {}", other, SHELLS.join(", "));
+      return 1;
+    }
+  };
+  print!("{}", script);
+  0
+}
+
+///Prints the candidates of one kind of completion, one per line as
+///the value, a tab and a description.  Outside a repository there is
+///nothing to list, which isn't an error: the shell just offers nothing.
+pub fn complete(args:&ParsedArgs) -> isize {
+  let candidates = match args.arg("kind") {
+    "commands" => command_candidates(),
+    kind => {
+      let repo = env::current_dir().map_err(RepositoryError::from).and_then(Repository::discover);
+      match repo {
+        Ok(repo) => match repository_candidates(&repo, kind) {
+          Some(candidates) => candidates,
+          None => return 1
+        },
+        Err(_) => vec!()
+      }
+    }
+  };
+  for (value, description) in candidates.into_iter() {
+    println!("{}\t{}", one_line(value.as_str()), one_line(description.as_str()));
+  }
+  0
+}
+
+fn visible_commands(commandList:&CommandList) -> Vec<&Command> {
+  let mut cmds:Vec<&Command> = commandList.values()
+                                          .filter(|cmd| !cmd.hidden())
+                                          .map(|cmd| &**cmd)
+                                          .collect();
+  cmds.sort_by(|a, b| a.name().cmp(b.name()));
+  cmds
+}
+
+fn command_candidates() -> Vec<(String, String)> {
+  let commandList = commands::standard_commands();
+  visible_commands(&commandList).iter()
+                                .map(|cmd| (cmd.name().to_string(), cmd.summary().to_string()))
+                                .collect()
+}
+
+fn repository_candidates(repo:&Repository, kind:&str) -> Option<Vec<(String, String)>> {
+  match kind {
+    "issues" => Some(repo.summaries().into_iter().map(|summary| {
+      let description = format!("{} [{}]", summary.title, summary.status);
+      (summary.id, description)
+    }).collect()),
+    "statuses" => {
+      //statuses are read relative to the repository root
+      if env::set_current_dir(repo.root()).is_err() {
+        return Some(vec!());
+      }
+      Some(status_storage::read_status_options().into_iter().map(|option| {
+        let category = option.category.as_str().to_string();
+        (option.name, category)
+      }).collect())
+    }
+    "tags" => Some(tag_counts(repo.summaries().as_slice()).into_iter().map(|(tag, count)| {
+      (tag, format!("{} issue{}", count, if count == 1 {""} else {"s"}))
+    }).collect()),
+    _ => None
+  }
+}
+
+fn tag_counts(summaries:&[IssueSummary]) -> BTreeMap<String, usize> {
+  let mut counts = BTreeMap::new();
+  for tag in summaries.iter().flat_map(|s| s.tags.iter()) {
+    *counts.entry(tag.clone()).or_insert(0) += 1;
+  }
+  counts
+}
+
+///Tabs and newlines would split a candidate in two.
+fn one_line(text:&str) -> String {
+  text.replace("\t", " ").replace("\n", " ")
+}
+
+fn options(cmd:&Command) -> Vec<&Arg> {
+  cmd.args().iter().filter(|a| a.is_option()).collect()
+}
+
+fn positionals(cmd:&Command) -> Vec<&Arg> {
+  cmd.args().iter().filter(|a| !a.is_option()).collect()
+}
+
+///Every spelling of the options of `cmd` which take a value.
+fn value_options(cmd:&Command) -> Vec<&'static str> {
+  options(cmd).into_iter()
+              .filter(|a| a.value.is_some())
+              .flat_map(|a| Some(a.name).into_iter().chain(a.alias.into_iter()))
+              .collect()
+}
+
+fn bash_action(completion:Completion) -> String {
+  match completion {
+    Completion::Nothing => "COMPREPLY=()".to_string(),
+    Completion::File => "COMPREPLY=( $(compgen -f -- \"$cur\") )".to_string(),
+    Completion::Values(values) =>
+      format!("COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )", values.join(" ")),
+    dynamic => format!("_evict_dynamic {}", dynamic.dynamic_kind().unwrap_or(""))
+  }
+}
+
+static BASH_HEADER:&'static str = r#"# bash completion for evict, generated by evict completions bash
+
+# The number of positional arguments before the word being completed.
+# $1 is the options which take a value, whose values aren't counted.
+_evict_position() {
+  local i=2 n=0
+  while [ $i -lt $COMP_CWORD ]; do
+    case " $1 " in
+      *" ${COMP_WORDS[i]} "*) i=$((i+1)) ;;
+      *) case "${COMP_WORDS[i]}" in
+           -?*) ;;
+           *) n=$((n+1)) ;;
+         esac ;;
+    esac
+    i=$((i+1))
+  done
+  echo $n
+}
+
+_evict_dynamic() {
+  local IFS=$'\n'
+  COMPREPLY=( $(compgen -W "$(evict __complete "$1" 2>/dev/null | cut -f1)" -- "$cur") )
+}
+
+_evict() {
+  local cur="${COMP_WORDS[COMP_CWORD]}"
+  local prev="${COMP_WORDS[COMP_CWORD-1]}"
+  if [ $COMP_CWORD -eq 1 ]; then
+    _evict_dynamic commands
+    return
+  fi
+  case "${COMP_WORDS[1]}" in
+"#;
+
+fn bash_script(cmds:&[&Command]) -> String {
+  let mut out = BASH_HEADER.to_string();
+  for cmd in cmds.iter() {
+    out.push_str(format!("    {})\n", cmd.name()).as_str());
+    let withValues:Vec<&Arg> = options(*cmd).into_iter().filter(|a| a.value.is_some()).collect();
+    if withValues.len() > 0 {
+      out.push_str("      case \"$prev\" in\n");
+      for option in withValues.iter() {
+        let pattern = match option.alias {
+          Some(alias) => format!("{}|{}", alias, option.name),
+          None => option.name.to_string()
+        };
+        out.push_str(format!("        {}) {}; return ;;\n", pattern,
+                             bash_action(option.completion)).as_str());
+      }
+      out.push_str("      esac\n");
+    }
+    let names:Vec<&str> = options(*cmd).into_iter()
+                                       .flat_map(|a| Some(a.name).into_iter().chain(a.alias.into_iter()))
+                                       .chain(Some("--help").into_iter())
+                                       .collect();
+    out.push_str("      if [[ \"$cur\" == -* ]]; then\n");
+    out.push_str(format!("        COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )\n",
+                         names.join(" ")).as_str());
+    out.push_str("        return\n      fi\n");
+    let args = positionals(*cmd);
+    if args.len() > 0 {
+      out.push_str(format!("      case $(_evict_position \"{}\") in\n",
+                           value_options(*cmd).join(" ")).as_str());
+      for (index, arg) in args.iter().enumerate() {
+        let pattern = if arg.rest {"*".to_string()} else {index.to_string()};
+        out.push_str(format!("        {}) {} ;;\n", pattern, bash_action(arg.completion)).as_str());
+      }
+      out.push_str("      esac\n");
+    }
+    out.push_str("      ;;\n");
+  }
+  out.push_str("  esac\n}\n\ncomplete -F _evict evict\n");
+  out
+}
+
+///Escapes text for a zsh _arguments spec inside single quotes.
+fn zsh_escape(text:&str) -> String {
+  text.replace("'", "'\\''").replace("[", "\\[").replace("]", "\\]").replace(":", "\\:")
+}
+
+fn zsh_action(completion:Completion) -> String {
+  match completion {
+    Completion::Nothing => "".to_string(),
+    Completion::File => "_files".to_string(),
+    Completion::Values(values) => format!("({})", values.join(" ")),
+    dynamic => format!("_evict_dynamic {}", dynamic.dynamic_kind().unwrap_or(""))
+  }
+}
+
+static ZSH_HEADER:&'static str = r#"#compdef evict
+# zsh completion for evict, generated by evict completions zsh
+
+_evict_dynamic() {
+  local -a items
+  items=(${(f)"$(evict __complete $1 2>/dev/null | tr '\t' ':')"})
+  _describe -t $1 $1 items
+}
+
+_evict() {
+  if (( CURRENT == 2 )); then
+    _evict_dynamic commands
+    return
+  fi
+  local cmd=$words[2]
+  shift words
+  (( CURRENT-- ))
+  case $cmd in
+"#;
+
+fn zsh_script(cmds:&[&Command]) -> String {
+  let mut out = ZSH_HEADER.to_string();
+  for cmd in cmds.iter() {
+    let mut specs:Vec<String> = vec!();
+    for option in options(*cmd).into_iter() {
+      let help = zsh_escape(option.help);
+      for name in Some(option.name).into_iter().chain(option.alias.into_iter()) {
+        specs.push(match option.value {
+          Some(value) => format!("'*{}[{}]:{}:{}'", name, help, zsh_escape(value),
+                                 zsh_action(option.completion)),
+          None => format!("'{}[{}]'", name, help)
+        });
+      }
+    }
+    for (index, arg) in positionals(*cmd).into_iter().enumerate() {
+      let position = if arg.rest {
+        "*:".to_string()
+      }else if arg.required {
+        format!("{}:", index + 1)
+      }else{
+        format!("{}::", index + 1)
+      };
+      specs.push(format!("'{}{}:{}'", position, zsh_escape(arg.name), zsh_action(arg.completion)));
+    }
+    out.push_str(format!("    {})\n", cmd.name()).as_str());
+    if specs.len() == 0 {
+      out.push_str("      ;;\n");
+    }else{
+      out.push_str(format!("      _arguments \\\n        {} ;;\n",
+                           specs.join(" \\\n        ")).as_str());
+    }
+  }
+  out.push_str("  esac\n}\n\n_evict \"$@\"\n");
+  out
+}
+
+///Escapes text for a single-quoted fish string.
+fn fish_escape(text:&str) -> String {
+  text.replace("\\", "\\\\").replace("'", "\\'")
+}
+
+///The fish `complete` flags which offer `completion`.
+fn fish_action(completion:Completion) -> String {
+  match completion {
+    Completion::Nothing => "".to_string(),
+    Completion::File => " -F".to_string(),
+    Completion::Values(values) => format!(" -a '{}'", values.join(" ")),
+    dynamic => format!(" -a '(__evict_dynamic {})'", dynamic.dynamic_kind().unwrap_or(""))
+  }
+}
+
+static FISH_HEADER:&'static str = r#"# fish completion for evict, generated by evict completions fish
+
+function __evict_dynamic
+  evict __complete $argv[1] 2>/dev/null
+end
+
+# Whether the command line is for the command $argv[1]
+function __evict_using
+  set -l tokens (commandline -opc)
+  test (count $tokens) -ge 2; and test "$tokens[2]" = $argv[1]
+end
+
+# Whether the word being completed is positional argument $argv[2]
+# (or later, if $argv[3] is rest) of the command $argv[1].  The rest
+# of $argv are the options which take a value.
+function __evict_arg
+  set -l tokens (commandline -opc)
+  test (count $tokens) -ge 2; and test "$tokens[2]" = $argv[1]; or return 1
+  set -e tokens[1 2]
+  set -l valueOptions $argv
+  set -e valueOptions[1 2 3]
+  set -l n 0
+  set -l skip 0
+  for t in $tokens
+    if test $skip = 1
+      set skip 0
+    else if contains -- $t $valueOptions
+      set skip 1
+    else if not string match -q -- '-?*' $t
+      set n (math $n + 1)
+    end
+  end
+  if test $argv[3] = rest
+    test $n -ge $argv[2]
+  else
+    test $n -eq $argv[2]
+  end
+end
+
+complete -c evict -f
+complete -c evict -n __fish_use_subcommand -a '(__evict_dynamic commands)'
+"#;
+
+fn fish_script(cmds:&[&Command]) -> String {
+  let mut out = FISH_HEADER.to_string();
+  for cmd in cmds.iter() {
+    out.push_str("\n");
+    for option in options(*cmd).into_iter() {
+      let mut flags = format!("-l {}", option.name.trim_left_matches('-'));
+      if let Some(alias) = option.alias {
+        let short = alias.trim_left_matches('-');
+        flags.push_str(format!(" {} {}", if short.len() == 1 {"-s"} else {"-o"}, short).as_str());
+      }
+      if option.value.is_some() {
+        flags.push_str(" -r");
+        flags.push_str(fish_action(option.completion).as_str());
+      }
+      out.push_str(format!("complete -c evict -n '__evict_using {}' {} -d '{}'\n",
+                           cmd.name(), flags, fish_escape(option.help)).as_str());
+    }
+    let valueOptions = value_options(*cmd).join(" ");
+    for (index, arg) in positionals(*cmd).into_iter().enumerate() {
+      if arg.completion == Completion::Nothing {
+        continue;
+      }
+      out.push_str(format!("complete -c evict -n '__evict_arg {} {} {} {}'{}\n",
+                           cmd.name(), index, if arg.rest {"rest"} else {"exact"}, valueOptions,
+                           fish_action(arg.completion)).as_str());
+    }
+  }
+  out
+}
+
+#[test]
+pub fn scripts_cover_every_command(){
+  let commandList = commands::standard_commands();
+  let cmds = visible_commands(&commandList);
+  let scripts = [bash_script(cmds.as_slice()), zsh_script(cmds.as_slice()),
+                 fish_script(cmds.as_slice())];
+  for script in scripts.iter() {
+    assert!(script.contains("__complete"));
+    assert!(!script.contains("__complete)"));
+    for cmd in cmds.iter() {
+      assert!(script.contains(format!(" {}", cmd.name()).as_str()));
+    }
+  }
+  assert!(scripts[0].contains("--status) _evict_dynamic statuses; return ;;"));
+  assert!(scripts[1].contains("'1:issue-id:_evict_dynamic issues'"));
+  assert!(scripts[2].contains("complete -c evict -n '__evict_using comment' -l message -s m -r"));
+}
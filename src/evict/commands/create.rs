@@ -16,105 +16,250 @@
  *   You should have received a copy of the GNU General Public License
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
-use fsm::NextState::*;
-use fsm::*;
-use issue::Issue;
-use file_manager;
-use file_util;
+use evict::issue::{Issue, IssueTag, IssueField, IssueAssignment};
+use evict::file_manager::IssueChange;
+use evict::file_util;
 use commands;
+use commands::ParsedArgs;
+use evict::author::Author;
 
-use status_storage;
+use evict::status_storage;
+use evict::custom_fields;
+use evict::templates;
+use evict::templates::IssueTemplate;
+use evict::config::Config;
 
-use serde_json::Result as SerdeResult;
+use evict::repository::RepositoryResult;
 
 // EVICT-BT-ID: 1399720517980750949
 // [conventions] bodyFile in Flags should be body_file
 
-static DEFAULT_ISSUE_BODY_FILE:&'static str = "ISSUE_MSG";
+static EDITOR_FILE_NAME:&'static str = "ISSUE_MSG";
 struct Flags{
   hasBody:bool,
   bodyFile:Option<String>,
+  body:Option<String>,
   title:Option<String>,
   author:Option<String>,
+  template:Option<String>,
+  noTemplate:bool,
+  tags:Vec<String>,
+  status:Option<String>,
+  assignee:Option<String>,
+  noInput:bool,
 }
 
-fn std_handler(flags:Flags, input:String) -> NextState<Flags,String> {
-  match input.as_str() {
-    "--no-body" => Continue(Flags{hasBody:false, 
-                                         .. flags}),
-    "--body-file" => ChangeState(get_body_file, flags),
-    "--title" => ChangeState(get_title, flags),
-    "--author" => ChangeState(get_author, flags),
-    _ => Continue(flags)
+impl Flags{
+  fn from_args(args:&ParsedArgs) -> Flags {
+    let value = |name:&str| args.value(name).map(|v| v.to_string());
+    Flags{hasBody:!args.has("--no-body"),
+          bodyFile:value("--body-file"),
+          body:value("--body"),
+          title:value("--title"),
+          author:value("--author"),
+          template:value("--template"),
+          noTemplate:args.has("--no-template"),
+          tags:args.values("--tag").into_iter().map(|t| t.to_string()).collect(),
+          status:value("--status"),
+          assignee:value("--assignee"),
+          noInput:args.has("--no-input")}
   }
 }
-fn get_body_file(flags:Flags, input:String) -> NextState<Flags, String> {
-  ChangeState(std_handler, Flags{bodyFile:Some(input), .. flags})
-}
-fn get_title(flags:Flags, input:String) -> NextState<Flags, String> {
-  ChangeState(std_handler, Flags{title:Some(input), .. flags})
-}
-fn get_author(flags:Flags, input:String) -> NextState<Flags, String> {
-  ChangeState(std_handler, Flags{author:Some(input), .. flags})
+
+pub fn create_issue(args:&ParsedArgs) -> isize {
+  let finalFlags = Flags::from_args(args);
+  match build_issue(&finalFlags) {
+    Ok(issue) => match write_issue(issue.clone()) {
+      Ok(_) => {
+        println!("Issue {} created.", issue.id()); 
+        0
+      }
+      Err(_) => {
+        println!("Issue creation failed.");
+        1
+      }
+    },
+    Err(msg) => {
+      println!("{}", msg);
+      2
+    }
+  }
 }
 
-pub fn create_issue(args:Vec<String>) -> isize {
-  let mut stateMachine = StateMachine::new(std_handler, 
-                                           Flags{hasBody:true, 
-                                                 bodyFile:None, 
-                                                 title:None,
-	                                         author:None});
-  for argVal in args.into_iter() {
-    stateMachine.process(argVal);
+///Gathers everything needed for the new issue, from the flags or
+///by asking the user.  With --no-input, anything which would need
+///asking for is an error instead.
+fn build_issue(flags:&Flags) -> Result<Issue, String> {
+  let template = try!(choose_template(flags));
+  let status = match flags.status {
+    Some(ref name) => Some(try!(status_storage::find_status_option(name.as_str())
+                                .ok_or(format!("Status {} does not exist", name)))),
+    None => None
   };
-  let finalFlags = stateMachine.extract_state();
-  let title = match finalFlags.title {
+  let mut title = match flags.title {
     Some(ref titleVal) => titleVal.to_string(),
+    None if flags.noInput => return Err("A title must be given with --title".to_string()),
     None => commands::prompt("Title: ")
   };
-  let author = match finalFlags.author {
-    Some(ref authorVal) => authorVal.to_string(),
+  for prefix in template.as_ref().and_then(|t| t.title_prefix.as_ref()).iter() {
+    if !title.starts_with(prefix.as_str()) {
+      title = format!("{} {}", prefix, title);
+    }
+  }
+  let author = match flags.author {
+    Some(ref authorVal) => Author::parse(authorVal.as_str()),
+    None if flags.noInput => try!(commands::find_author().ok_or(
+      "No author is configured.  Use --author or set a default author.".to_string())),
     None => commands::get_author()
   };
-  let mut editedBodyFile = false;
-  let bodyFile = if finalFlags.hasBody && finalFlags.bodyFile.is_none() {
-    editedBodyFile =  commands::edit_file(DEFAULT_ISSUE_BODY_FILE);
-    if !editedBodyFile {
-      return 2;
+  let assignee = flags.assignee.as_ref().map(|name| {
+    if name.as_str() == "me" {author.clone()} else {Author::parse(name.as_str())}
+  });
+  let body = try!(read_body(flags, template.as_ref()));
+
+  let mut issue = Issue::new(title, body, author.clone());
+  issue.status = status_storage::read_default_status().make_status();
+  for t in template.iter() {
+    apply_template(&mut issue, t, &author);
+  }
+  for s in status.iter() {
+    issue.status = s.make_status();
+  }
+  for tag in flags.tags.iter() {
+    if !issue.all_tags().contains(tag) {
+      issue.add_tag(IssueTag::new(tag.clone(), author.clone(), true));
     }
-    Some(DEFAULT_ISSUE_BODY_FILE.to_string())
-  }else if !finalFlags.hasBody {
-    None
-  }else{
-    finalFlags.bodyFile
+  }
+  if assignee.is_some() {
+    issue.assign(IssueAssignment::new(assignee, author.clone()));
+  }
+  Ok(issue)
+}
+
+///Reads the issue body from --body, --body-file or stdin if one of
+///those was given, or from the editor otherwise.
+fn read_body(flags:&Flags, template:Option<&IssueTemplate>) -> Result<String, String> {
+  if !flags.hasBody {
+    return Ok("".to_string());
+  }
+  let given = match (flags.body.as_ref(), flags.bodyFile.as_ref()) {
+    (Some(text), _) if text.as_str() != "-" => Some(text.clone()),
+    (Some(_), _) => Some("-".to_string()),
+    (None, Some(filename)) => Some(filename.clone()),
+    (None, None) => None
   };
-  let created = do_issue_creation(title, author, bodyFile);
-  if editedBodyFile { file_util::delete_file(DEFAULT_ISSUE_BODY_FILE); };
-  if created.is_ok() {
-    println!("Issue {} created.", created.unwrap().id()); 
-    0
-  }else{
-    println!("Issue creation failed.");
-    1
+  match given {
+    //--body - and --body-file - both read stdin
+    Some(ref source) if source.as_str() == "-" =>
+      commands::read_stdin().ok_or("Could not read the issue body from stdin".to_string()),
+    Some(source) => if flags.body.is_some() {
+      Ok(source)
+    }else{
+      file_util::read_string_from_file(source.as_str())
+                .map_err(|_| format!("Could not read issue body from {}", source))
+    },
+    None if flags.noInput =>
+      Err("A body must be given with --body, --body-file or --no-body".to_string()),
+    None => {
+      let initial = template.map_or("", |t| t.body.as_str());
+      match commands::edit_text(EDITOR_FILE_NAME, initial) {
+        Some(text) => Ok(if template.is_some() {templates::strip_guidance(text.as_str())} else {text}),
+        None => Err("No issue body was written".to_string())
+      }
+    }
   }
 }
 
-fn do_issue_creation(title:String, author:String, bodyFile:Option<String>) -> SerdeResult<Issue>{
-  let mut issue = try!(if bodyFile.is_none() {
-                   Ok(Issue::new(title, "".to_string(), author))
-                 }else{
-                   let bodyTextOpt = file_util::read_string_from_file(bodyFile.unwrap().as_str());
-                   bodyTextOpt.map(
-                     |text| Issue::new(title.clone(), text, author.clone())
-                   )
-                 });
-  issue.status = status_storage::read_default_status().make_status();
-  write_issue(issue.clone()).map(|_| issue)
+///Finds the template to create the issue from.  A template given
+///with --template is always used, followed by `default_template` from
+///the config.  Otherwise, the user picks one if there are any.
+fn choose_template(flags:&Flags) -> Result<Option<IssueTemplate>, String> {
+  let name = match flags.template {
+    Some(ref name) => Some(name.clone()),
+    None if flags.noTemplate => None,
+    None => match Config::load().default_template {
+      Some(name) => Some(name),
+      None => {
+        let names = templates::template_names();
+        if names.len() > 0 && flags.noInput {
+          return Err(format!("Choose a template with --template (one of {}), or use --no-template",
+                             names.join(", ")));
+        }else if names.len() > 0 {
+          prompt_for_template(names.as_slice())
+        }else{
+          None
+        }
+      }
+    }
+  };
+  match name {
+    Some(name) => {
+      let template = try!(templates::load_template(name.as_str())
+                          .map_err(|_| format!("Could not read template {}", name)));
+      try!(check_template(&template));
+      Ok(Some(template))
+    }
+    None => Ok(None)
+  }
 }
 
-fn write_issue(issue:Issue) -> SerdeResult<()> {
-  let mut committable = file_manager::read_issues();
-  committable.push(issue);
-  file_manager::write_issues(committable.as_slice())
+fn prompt_for_template(names:&[String]) -> Option<String> {
+  println!("Templates:");
+  println!("  0: (no template)");
+  for (index, name) in names.iter().enumerate() {
+    println!("  {}: {}", index + 1, name);
+  }
+  loop {
+    let choice = commands::prompt("Template: ");
+    match usize::from_str_radix(choice.trim(), 10) {
+      Ok(0) => return None,
+      Ok(index) if index <= names.len() => return Some(names[index - 1].clone()),
+      _ => match names.iter().find(|name| name.as_str() == choice.trim()) {
+        Some(name) => return Some(name.clone()),
+        None => println!("{} is not one of the templates", choice)
+      }
+    }
+  }
 }
 
+///Checks that the status and fields set by a template are valid,
+///so problems show up before the user writes the issue body.
+fn check_template(template:&IssueTemplate) -> Result<(), String> {
+  for status in template.status.iter() {
+    if status_storage::find_status_option(status.as_str()).is_none() {
+      return Err(format!("Template {} has status {}, which does not exist", template.name, status));
+    }
+  }
+  for &(ref name, ref value) in template.fields.iter() {
+    match custom_fields::find_field(name.as_str()) {
+      Some(field) => {
+        try!(field.validate(value.as_str())
+                  .map_err(|msg| format!("Template {}: {}", template.name, msg)));
+      }
+      None => return Err(format!("Template {} sets {}, which is not a custom field",
+                                 template.name, name))
+    }
+  }
+  Ok(())
+}
+
+fn apply_template(issue:&mut Issue, template:&IssueTemplate, author:&Author) {
+  for status in template.status.as_ref().and_then(|s| status_storage::find_status_option(s.as_str())).iter() {
+    issue.status = status.make_status();
+  }
+  for tag in template.tags.iter() {
+    issue.add_tag(IssueTag::new(tag.clone(), author.clone(), true));
+  }
+  for &(ref name, ref value) in template.fields.iter() {
+    //check_template has already validated these
+    let normalized = custom_fields::find_field(name.as_str())
+                                   .and_then(|field| field.validate(value.as_str()).ok());
+    if let Some(normalized) = normalized {
+      issue.set_field(IssueField::new(name.clone(), Some(normalized), author.clone()));
+    }
+  }
+}
+
+fn write_issue(issue:Issue) -> RepositoryResult<()> {
+  commands::save_changes(&[IssueChange::created(issue)])
+}
@@ -16,24 +16,22 @@
  *   You should have received a copy of the GNU General Public License
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
-use config;
+use evict::config;
 use std::error::Error;
+use commands::ParsedArgs;
 
-pub fn default_author(mut args:Vec<String>) -> isize {
-  if args.len() > 1 {
-    println!("default-author usage: evict default-author [new-author]");
-    1
-  }else{
-    let config = config::Config::load(); 
-    if args.len() == 0 {
+pub fn default_author(args:&ParsedArgs) -> isize {
+  let config = config::Config::load(); 
+  match args.value("author") {
+    None => {
       match config.author {
         Some(author) => println!("{}", author),
         None => println!("No author set")
       };
       0
-    }else{
-      //How do we get values out of a Vec nicely?  Can't move when indexing...
-      let save_result = config::Config{author:Some(args.swap_remove(0)), .. config}.save();
+    }
+    Some(author) => {
+      let save_result = config::Config{author:Some(author.to_string()), .. config}.save();
       match save_result {
         Err(e) => println!("Failed to save config: {}", e.description()),
         _ => {}
@@ -42,4 +40,3 @@ pub fn default_author(mut args:Vec<String>) -> isize {
     }
   }
 }
-
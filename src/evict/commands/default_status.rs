@@ -17,21 +17,18 @@
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use status_storage;
+use evict::status_storage;
+use commands::ParsedArgs;
 
-pub fn default_status(mut args:Vec<String>) -> isize {
-  if args.len() > 1 {
-    println! ("default-status usage: evict default-status [new-status]");
-    1
-  }else{
-    if args.len() == 0 {
+pub fn default_status(args:&ParsedArgs) -> isize {
+  match args.value("status") {
+    None => {
       let default = status_storage::read_default_status();
       println!("Current default status is: {}", default.name);
       2
-    }else{
-      // w.r.t the swap_remove - there has got to be a better way to take an
-      // element out of a vector. Would make it immut, too.
-      let status = status_storage::StatusOption{name:args.swap_remove(0)};
+    }
+    Some(name) => {
+      let status = status_storage::StatusOption::new(name.to_string());
       
       match status_storage::write_default_status(&status) {
         Ok(_) => 0,
@@ -0,0 +1,103 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use commands;
+use commands::ParsedArgs;
+use commands::link::single_match;
+use evict::file_manager::IssueChange;
+use evict::issue::{Issue, IssueLink, IssueComment};
+use evict::issue::IssueTimelineEvent::TimelineComment;
+use evict::links::LinkType;
+use evict::status_storage;
+use evict::status_storage::StatusCategory;
+
+pub fn mark_duplicate(args:&ParsedArgs) -> isize {
+  let (duplicateId, canonicalId) = (args.arg("duplicate-id"), args.arg("canonical-id"));
+  let repo = match commands::repository() {
+    Ok(repo) => repo,
+    Err(e) => {println!("{}", e); return 2}
+  };
+  let (mut duplicate, mut canonical) = match (single_match(duplicateId, &repo),
+                                              single_match(canonicalId, &repo)) {
+    (Some(dup), Some(canon)) => (dup, canon),
+    _ => return 2
+  };
+  let (originalDuplicate, originalCanonical) = (duplicate.clone(), canonical.clone());
+  if duplicate.id() == canonical.id() {
+    println!("An issue can't be a duplicate of itself");
+    return 3;
+  }
+
+  let author = commands::get_author();
+  let options = status_storage::read_status_options();
+  if !status_storage::category_of(duplicate.status.name.as_str(), options.as_slice()).is_closed() {
+    match options.iter().find(|x| x.category == StatusCategory::Closed) {
+      Some(closed) => duplicate.change_status(closed.make_status(), author.clone()),
+      None => {
+        println!("There is no closed status to give the duplicate.  Create one with evict new-status.");
+        return 4;
+      }
+    }
+  }
+
+  let duplicatesType = LinkType::Duplicates.as_str();
+  if !duplicate.has_link(duplicatesType, canonical.id()) {
+    duplicate.add_link(IssueLink::new(duplicatesType.to_string(), canonical.id().to_string(),
+                                      author, true));
+  }
+
+  let copied = copy_events(&duplicate, &mut canonical);
+
+  match commands::save(&repo, &[IssueChange::new(originalDuplicate, duplicate),
+                                IssueChange::new(originalCanonical, canonical)]) {
+    Ok(_) => {
+      println!("Marked {} as a duplicate of {} ({} events copied)", duplicateId, canonicalId, copied);
+      0
+    }
+    Err(e) => {println!("{}", e); 5}
+  }
+}
+
+///Copies comments and tags from one issue to another, skipping any
+///which were copied before.  Returns the number of events copied.
+fn copy_events(from:&Issue, to:&mut Issue) -> usize {
+  let mut copied = 0;
+  for evt in from.events.iter() {
+    let alreadyCopied = to.events.iter().any(|existing| {
+      existing.copied_from().map_or(false, |origin| {
+        origin.issue_id.as_str() == from.id() && origin.event_id.as_str() == evt.id()
+      })
+    });
+    if !alreadyCopied {
+      if let Some(copy) = evt.copy_from_issue(from.id()) {
+        //comments are copied as they currently read, and deleted ones not at all
+        let copy = match (evt, copy) {
+          (&TimelineComment(ref original), TimelineComment(comment)) =>
+            match from.comment_text(original) {
+              Some(text) => TimelineComment(IssueComment{body_text:text.to_string(), .. comment}),
+              None => continue
+            },
+          (_, other) => other
+        };
+        to.events.push(copy);
+        copied += 1;
+      }
+    }
+  }
+  copied
+}
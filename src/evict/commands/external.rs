@@ -0,0 +1,131 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use evict::config::Config;
+use evict::file_util;
+use evict::repository::Repository;
+use evict::evict::CURRENT_VERSION;
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+static EXTERNAL_PREFIX:&'static str = "evict-";
+
+///Expands the alias `name` from the repository's config into a
+///command and its arguments, followed by `args`.
+pub fn expand_alias(name:&str, repo:&Repository, args:Vec<String>) -> Option<(String, Vec<String>)> {
+  let config = Config::load_in(repo.evict_dir().as_path());
+  config.aliases.get(name).and_then(|expansion| {
+    let mut words = split_words(expansion.as_str());
+    if words.len() == 0 {
+      None
+    }else{
+      let command = words.remove(0);
+      words.extend(args.into_iter());
+      Some((command, words))
+    }
+  })
+}
+
+///Splits an alias into words at whitespace.  Quotes group words
+///containing spaces, as in `--format "{id} {title}"`.
+pub fn split_words(text:&str) -> Vec<String> {
+  let mut words = vec!();
+  let mut current = String::new();
+  let mut inWord = false;
+  let mut quote:Option<char> = None;
+  for c in text.chars() {
+    match quote {
+      Some(q) if c == q => quote = None,
+      Some(_) => current.push(c),
+      None if c == '"' || c == '\'' => {quote = Some(c); inWord = true;}
+      None if c.is_whitespace() => {
+        if inWord {
+          words.push(current.clone());
+          current.clear();
+          inWord = false;
+        }
+      }
+      None => {current.push(c); inWord = true;}
+    }
+  }
+  if inWord {
+    words.push(current);
+  }
+  words
+}
+
+///Finds the `evict-<name>` executable on PATH which runs the
+///external command `name`.
+pub fn find_external(name:&str) -> Option<PathBuf> {
+  if !is_command_name(name) {
+    return None;
+  }
+  let path = match env::var_os("PATH") {
+    Some(path) => path,
+    None => return None
+  };
+  let filename = format!("{}{}", EXTERNAL_PREFIX, name);
+  env::split_paths(&path).map(|dir| dir.join(filename.as_str()))
+                         .find(|candidate| file_util::is_executable(candidate))
+}
+
+///Whether `name` can name an external command.  Names which could
+///reach outside the PATH directories, such as `../x`, can't.
+fn is_command_name(name:&str) -> bool {
+  name.len() > 0 && !name.contains("..") &&
+    !name.contains('/') && !name.contains('\\')
+}
+
+///Runs an external command in the current directory.  It's told
+///where the repository is, if there is one, and which version of
+///the file format evict writes, so it can read the issues itself.
+pub fn run_external(executable:&PathBuf, args:Vec<String>, repo:Option<&Repository>) -> isize {
+  let mut command = process::Command::new(executable);
+  command.args(args.as_slice()).env("EVICT_FORMAT_VERSION", CURRENT_VERSION.to_string());
+  if let Some(repo) = repo {
+    command.env("EVICT_ROOT", repo.root()).env("EVICT_DIR", repo.evict_dir());
+  }
+  match command.status() {
+    Ok(status) => status.code().unwrap_or(1) as isize,
+    Err(e) => {
+      println!("Couldn't run {}: {}", executable.display(), e);
+      1
+    }
+  }
+}
+
+#[test]
+pub fn alias_words_respect_quotes(){
+  assert_eq!(split_words("list --assignee me  --open"),
+             vec!("list", "--assignee", "me", "--open"));
+  assert_eq!(split_words("list --format \"{id} {title}\" --tag ''"),
+             vec!("list", "--format", "{id} {title}", "--tag", ""));
+}
+
+#[test]
+pub fn command_names_stay_in_path_directories(){
+  assert!(is_command_name("report"));
+  assert!(is_command_name("sync-jira"));
+  assert!(!is_command_name(""));
+  assert!(!is_command_name("../../bin/sh"));
+  assert!(!is_command_name("sub/report"));
+  assert!(!is_command_name("sub\\report"));
+  assert!(!is_command_name(".."));
+}
@@ -0,0 +1,149 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use commands;
+use commands::list;
+use commands::ParsedArgs;
+use evict::issue::Issue;
+use evict::links;
+use evict::links::LinkType;
+use evict::status_storage;
+use evict::status_storage::{StatusOption, StatusCategory};
+
+use std::io::Write;
+use std::io::stderr;
+
+#[derive(PartialEq)]
+enum GraphFormat{
+  Dot,
+  Mermaid
+}
+
+pub fn graph(args:&ParsedArgs) -> isize {
+  //everything but --format is a list filter
+  let format = match args.value("--format") {
+    None | Some("dot") => GraphFormat::Dot,
+    Some("mermaid") => GraphFormat::Mermaid,
+    Some(_) => {
+      println!("graph usage: evict graph [--format dot|mermaid] [list options]");
+      return 1;
+    }
+  };
+
+  let repo = match commands::repository() {
+    Ok(repo) => repo,
+    Err(e) => {
+      println!("{}", e);
+      return 2;
+    }
+  };
+  let issues = list::select_issues(&repo, args);
+  let options = status_storage::read_status_options();
+
+  for cycle in links::blocking_cycles(issues.as_slice()).iter() {
+    let _ = writeln!(stderr(), "Warning: circular blocking chain: {}", cycle.join(" -> "));
+  }
+
+  let output = if format == GraphFormat::Dot {
+    dot_graph(issues.as_slice(), options.as_slice())
+  }else{
+    mermaid_graph(issues.as_slice(), options.as_slice())
+  };
+  println!("{}", output);
+  0
+}
+
+///Links between the given issues, as (source, link type, target).
+///Links to issues which weren't selected are left out.
+fn graph_edges(issues:&[Issue]) -> Vec<(String, LinkType, String)> {
+  let mut edges = vec!();
+  for issue in issues.iter() {
+    for (link_type, target) in issue.links().into_iter() {
+      let selected = issues.iter().any(|i| i.id() == target.as_str());
+      match LinkType::from_str(link_type.as_str()) {
+        Some(lt) if selected => edges.push((issue.id().to_string(), lt, target)),
+        _ => {}
+      }
+    }
+  }
+  edges
+}
+
+fn status_color(issue:&Issue, options:&[StatusOption]) -> &'static str {
+  match status_storage::category_of(issue.status.name.as_str(), options) {
+    StatusCategory::Open => "#c6e2ff",
+    StatusCategory::InProgress => "#fff3b0",
+    StatusCategory::Closed => "#d3d3d3"
+  }
+}
+
+fn link_color(link_type:LinkType) -> &'static str {
+  match link_type {
+    LinkType::Blocks => "#d62728",
+    LinkType::DependsOn => "#ff7f0e",
+    LinkType::Duplicates => "#7f7f7f",
+    LinkType::RelatesTo => "#1f77b4"
+  }
+}
+
+fn dot_graph(issues:&[Issue], options:&[StatusOption]) -> String {
+  let mut out = String::new();
+  out.push_str("digraph evict {\n");
+  out.push_str("  node [shape=box, style=filled];\n");
+  for issue in issues.iter() {
+    out.push_str(format!("  \"{}\" [label=\"{}\\n({})\", fillcolor=\"{}\"];\n",
+                         issue.id(), issue.title().replace("\\", "\\\\").replace("\"", "\\\""),
+                         issue.status.name, status_color(issue, options)).as_str());
+  }
+  for &(ref source, link_type, ref target) in graph_edges(issues).iter() {
+    let style = if link_type == LinkType::Duplicates || link_type == LinkType::RelatesTo {
+      "dashed"
+    }else{
+      "solid"
+    };
+    out.push_str(format!("  \"{}\" -> \"{}\" [label=\"{}\", color=\"{}\", style={}];\n",
+                         source, target, link_type.as_str(), link_color(link_type),
+                         style).as_str());
+  }
+  out.push_str("}");
+  out
+}
+
+fn mermaid_graph(issues:&[Issue], options:&[StatusOption]) -> String {
+  let mut out = String::new();
+  out.push_str("graph TD\n");
+  for issue in issues.iter() {
+    //mermaid node ids can't start with a digit
+    out.push_str(format!("  i{}[\"{}<br/>({})\"]\n",
+                         issue.id(), issue.title().replace("\"", "#quot;"),
+                         issue.status.name).as_str());
+    out.push_str(format!("  style i{} fill:{}\n", issue.id(), status_color(issue, options))
+                 .as_str());
+  }
+  for (index, &(ref source, link_type, ref target)) in graph_edges(issues).iter().enumerate() {
+    let arrow = if link_type == LinkType::Duplicates || link_type == LinkType::RelatesTo {
+      "-.->"
+    }else{
+      "-->"
+    };
+    out.push_str(format!("  i{} {}|{}| i{}\n", source, arrow, link_type.as_str(), target)
+                 .as_str());
+    out.push_str(format!("  linkStyle {} stroke:{}\n", index, link_color(link_type)).as_str());
+  }
+  out
+}
@@ -0,0 +1,75 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use commands;
+use commands::command;
+use commands::ParsedArgs;
+use commands::external;
+use evict::repository::Repository;
+
+use std::env;
+use std::iter;
+
+pub fn help(args:&ParsedArgs) -> isize {
+  let commandList = commands::standard_commands();
+  match args.value("command") {
+    Some(name) => match commandList.get(name) {
+      Some(cmd) => {
+        command::print_help(&**cmd);
+        0
+      }
+      None => {
+        let alias = env::current_dir().ok()
+                                      .and_then(|dir| Repository::discover(dir).ok())
+                                      .and_then(|repo| external::expand_alias(name, &repo, vec!()));
+        match alias {
+          Some((command, args)) => {
+            println!("{} is an alias for {} {}", name, command, args.join(" "));
+            0
+          }
+          None => {
+            println!("Command {} not found", name);
+            1
+          }
+        }
+      }
+    },
+    None => {
+      print_overview(&commandList);
+      0
+    }
+  }
+}
+
+///Lists every command with its summary.
+pub fn print_overview(commandList:&commands::CommandList) {
+  let mut names:Vec<&String> = commandList.iter()
+                                         .filter(|&(_, cmd)| !cmd.hidden())
+                                         .map(|(name, _)| name)
+                                         .collect();
+  names.sort();
+  let width = names.iter().map(|n| n.len()).max().unwrap_or(0);
+  println!("usage: evict <command> [<args>]");
+  println!("");
+  for name in names.into_iter() {
+    let padding:String = iter::repeat(' ').take(width - name.len()).collect();
+    println!("    {}{}  {}", name, padding, commandList[name].summary());
+  }
+  println!("");
+  println!("Run evict help <command> or evict <command> --help for a command's arguments.");
+}
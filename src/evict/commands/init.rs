@@ -16,21 +16,28 @@
  *   You should have received a copy of the GNU General Public License
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
-use file_manager;
-use status_storage::{StatusOption, 
+use evict::file_manager;
+use evict::status_storage::{StatusOption,
+                     StatusCategory,
                      write_status_options, 
                      write_default_status};
-use file_util;
+use evict::file_util;
+use commands::ParsedArgs;
+use evict::issue_cache;
 
-pub fn initialize(_:Vec<String>) -> isize {
+use std::path::Path;
+
+pub fn initialize(_:&ParsedArgs) -> isize {
   let createSuccess = file_util::create_directory(file_manager::EVICT_DIRECTORY);
   if createSuccess {
-    let defaultStatus = StatusOption{name:"open".to_string()};
-    let statusOpts = vec!(defaultStatus.clone(), StatusOption{name:"closed".to_string()});
+    let defaultStatus = StatusOption{name:"open".to_string(), category:StatusCategory::Open};
+    let statusOpts = vec!(defaultStatus.clone(),
+                          StatusOption{name:"closed".to_string(), category:StatusCategory::Closed});
     let optionSuccess = write_status_options(statusOpts);
     if optionSuccess.is_ok() {
       let defaultResult = write_default_status(&defaultStatus);
       if defaultResult.is_ok() {
+        issue_cache::ignore_in_git(Path::new(file_manager::EVICT_DIRECTORY));
         let idirSuccess = file_util::create_directory(
                                           file_manager::issue_directory().as_str());
         if idirSuccess {0}else{1}
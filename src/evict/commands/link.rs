@@ -0,0 +1,90 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use evict::issue::{IssueLink, Issue};
+use commands;
+use commands::ParsedArgs;
+use evict::file_manager::IssueChange;
+use evict::repository::Repository;
+use evict::links::{LinkType, ALL_LINK_TYPES};
+
+pub fn link(args:&ParsedArgs) -> isize {
+  link_cmd(args, "link", true)
+}
+
+pub fn unlink(args:&ParsedArgs) -> isize {
+  link_cmd(args, "unlink", false)
+}
+
+fn link_cmd(args:&ParsedArgs, cmdName:&str, enabledAfter:bool) -> isize {
+  let linkType = match LinkType::from_str(args.arg("link-type")) {
+    Some(linkType) => linkType,
+    None => {
+      let names:Vec<&str> = ALL_LINK_TYPES.iter().map(|x| x.as_str()).collect();
+      println!("{} usage: evict {} <issue-id> <link-type> <issue-id>", cmdName, cmdName);
+      println!("    Where <link-type> is one of: {}", names.join(", "));
+      return 1;
+    }
+  };
+  let repo = match commands::repository() {
+    Ok(repo) => repo,
+    Err(e) => {println!("{}", e); return 2}
+  };
+  let target = match single_match(args.arg("target-id"), &repo) {
+    Some(target) => target,
+    None => return 2
+  };
+  let source = match single_match(args.arg("issue-id"), &repo) {
+    Some(source) => source,
+    None => return 2
+  };
+  if source.id() == target.id() {
+    println!("An issue can't be linked to itself");
+    return 3;
+  }
+  if source.has_link(linkType.as_str(), target.id()) == enabledAfter {
+    println!("Nothing to do: {} {} {} {}", source.id(),
+             if enabledAfter {"already"} else {"doesn't"}, linkType.as_str(), target.id());
+    return 0;
+  }
+
+  let targetId = target.id().to_string();
+  let change = IssueChange::apply(source, |mut issue| {
+    issue.add_link(IssueLink::new(linkType.as_str().to_string(), targetId,
+                                  commands::get_author(), enabledAfter));
+    issue
+  });
+  match commands::save(&repo, &[change]) {
+    Ok(_) => 0,
+    Err(e) => {
+      println!("{}", e);
+      4
+    }
+  }
+}
+
+pub fn single_match(idPart:&str, repo:&Repository) -> Option<Issue> {
+  //links are made and removed on the issues named, even duplicates
+  match repo.find_direct(idPart) {
+    Ok(issue) => Some(issue),
+    Err(e) => {
+      println!("{}", e);
+      None
+    }
+  }
+}
@@ -16,17 +16,28 @@
  *   You should have received a copy of the GNU General Public License
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
-use file_manager;
-use issue;
-use issue::{Issue};
-use issue::IssueTimelineEvent::{TimelineComment};
+use evict::file_manager::ReadParts;
+use evict::issue;
+use evict::issue::{Issue, IssueComment};
+use evict::issue::IssueTimelineEvent::{TimelineComment};
 
-use fsm::*;
-use fsm::NextState::*;
-use selection;
-use date_sort;
+use evict::selection;
+use commands;
+use commands::{Arg, Completion, ParsedArgs};
+use evict::date_sort;
+use evict::author::Mailmap;
+use evict::status_storage;
+use evict::status_storage::StatusOption;
+use evict::priority::PriorityScale;
+use evict::links;
+use evict::markdown;
+use evict::repository::Repository;
+use evict::issue_cache::IssueSummary;
+use evict::custom_fields;
 
 use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::iter::repeat;
 
 trait LinePushingString{
   fn push_strln<S:Borrow<str>>(&mut self, rhs:S);
@@ -40,20 +51,114 @@ impl LinePushingString for String{
 }
 
 
-pub fn list_issues(args:Vec<String>) -> isize{
-  let mut stateMachine = StateMachine::new(std_handler,
-                                                Flags{short:false,
-                                                      statuses:vec!(),
-                                                      noComments:false,
-                                                      id:None,
-                                                      tags:vec!()});
+pub fn list_issues(args:&ParsedArgs) -> isize{
+  let final_flags = parse_flags(args);
+  let repo = match commands::repository() {
+    Ok(repo) => repo,
+    Err(e) => {
+      println!("{}", e);
+      return 1;
+    }
+  };
+  if summaries_suffice(&final_flags) {
+    let summaries = filter_summaries(repo.summaries(), &final_flags);
+    println!("{}", print_summaries(summaries));
+    return 0;
+  }
+  let mailmap = Mailmap::load();
+  let parts = if needs_events(&final_flags) {ReadParts::Everything} else {ReadParts::BodyOnly};
+  let issues = match parts {
+    ReadParts::Everything => filter_issues(repo.issues(), &final_flags),
+    ReadParts::BodyOnly => {
+      //issues are read one at a time and only kept if they match
+      let options = status_storage::read_status_options();
+      repo.reader(ReadParts::BodyOnly)
+        .filter(|issue| matches_body(issue, &final_flags, &mailmap, options.as_slice()))
+        .collect()
+    }
+  };
+
+  let to_print = print_issue_vec(issues, &final_flags, &mailmap);
+  println!("{}", to_print);
+  0
+}
+
+///The options taken by list, and by graph to choose its issues.
+pub fn list_args() -> Vec<Arg> {
+  vec!(Arg::flag("--short", "Print only IDs and titles").alias("-s"),
+       Arg::flag("--nocomment", "Leave out comments"),
+       Arg::flag("--raw", "Print Markdown as written"),
+       Arg::option("--id", "issue-id", "Only the issue whose ID ends with this")
+         .completes(Completion::Issue),
+       Arg::option("--status", "status", "Only issues with this status; may be repeated")
+         .completes(Completion::Status),
+       Arg::option("--tag", "tag", "Only issues with this tag; may be repeated")
+         .completes(Completion::Tag),
+       Arg::option("--author", "author", "Only issues by this author; may be repeated"),
+       Arg::option("--assignee", "author", "Only issues assigned to this author, or me"),
+       Arg::flag("--open", "Only issues which aren't closed"),
+       Arg::flag("--closed", "Only closed issues"),
+       Arg::option("--priority", "level", "Only issues with this priority; may be repeated"),
+       Arg::option("--min-priority", "level", "Only issues with at least this priority"),
+       Arg::option("--milestone", "name", "Only issues in this milestone"),
+       Arg::option("--field", "name=value", "Only issues with this field value"),
+       Arg::flag("--blocked", "Only issues waiting on open blockers"),
+       Arg::flag("--ready", "Only open issues with no open blockers"),
+       Arg::option("--sort", "date|priority|field:<name>", "The order to list issues in"),
+       Arg::option("--format", "template", "Print each issue using a template"))
+}
 
-  for arg in args.into_iter(){
-    stateMachine.process(arg);
+///Reads all issues and returns the ones selected by the `evict list`
+///filter options in `args`.  Options that only change how issues are
+///displayed are ignored.
+pub fn select_issues(repo:&Repository, args:&ParsedArgs) -> Vec<Issue> {
+  let flags = parse_flags(args);
+  filter_issues(repo.issues(), &flags)
+}
+
+fn parse_flags(args:&ParsedArgs) -> Flags {
+  let strings = |name:&str| args.values(name).into_iter().map(|v| v.to_string()).collect::<Vec<String>>();
+  let value = |name:&str| args.value(name).map(|v| v.to_string());
+  Flags{short:args.has("--short"),
+        statuses:strings("--status"),
+        noComments:args.has("--nocomment"),
+        id:value("--id"),
+        tags:strings("--tag"),
+        authors:strings("--author"),
+        assignees:strings("--assignee"),
+        open:args.has("--open"),
+        closed:args.has("--closed"),
+        priorities:strings("--priority"),
+        minPriority:value("--min-priority"),
+        sort:args.value("--sort").map_or(SortKey::Date, parse_sort),
+        fields:args.values("--field").into_iter().filter_map(parse_field).collect(),
+        template:value("--format"),
+        milestone:value("--milestone"),
+        blocked:args.has("--blocked"),
+        ready:args.has("--ready"),
+        raw:args.has("--raw")}
+}
+
+fn parse_sort(input:&str) -> SortKey {
+  //date is the default sort order, so anything unknown is ignored
+  if input == "priority" {
+    SortKey::Priority
+  }else if input.starts_with("field:") {
+    SortKey::Field(input["field:".len() ..].to_string())
+  }else{
+    SortKey::Date
+  }
+}
+
+fn parse_field(input:&str) -> Option<(String, String)> {
+  //filters are given as name=value, anything else is ignored
+  input.find('=').map(|pos| (input[.. pos].to_string(), input[pos + 1 ..].to_string()))
+}
+
+fn filter_issues(mut issues:Vec<Issue>, final_flags:&Flags) -> Vec<Issue> {
+  if final_flags.blocked || final_flags.ready {
+    issues = filter_blocked(issues, final_flags);
   }
-  let final_flags = stateMachine.extract_state();
-  
-  let mut issues = file_manager::read_issues();
 
   for id in final_flags.id.iter() {
     issues = selection::find_matching_issues(id.as_str(), issues.as_slice());
@@ -70,9 +175,113 @@ pub fn list_issues(args:Vec<String>) -> isize{
     found
   }).collect(); 
 
-  let to_print = print_issue_vec(issues, &final_flags);
-  println!("{}", to_print);
-  0
+  for milestone in final_flags.milestone.iter() {
+    issues = issues.into_iter()
+                   .filter(|check| check.milestone() == Some(milestone.as_str()))
+                   .collect();
+  }
+
+  if final_flags.assignees.len() > 0 {
+    let mailmap = Mailmap::load();
+    let me = commands::find_author().map(|author| mailmap.canonical(&author));
+    issues = issues.into_iter().filter(|check| {
+      match check.assignee().map(|assignee| mailmap.canonical(assignee)) {
+        Some(assignee) => final_flags.assignees.iter().any(|ident| {
+          if ident.as_str() == "me" {
+            me.as_ref() == Some(&assignee)
+          }else{
+            assignee.matches(ident.as_str())
+          }
+        }),
+        None => false
+      }
+    }).collect();
+  }
+
+  if final_flags.fields.len() > 0 {
+    issues = issues.into_iter().filter(|check| {
+      final_flags.fields.iter().all(|&(ref name, ref value)| matches_field(check, name, value))
+    }).collect();
+  }
+
+  let options = status_storage::read_status_options();
+  let scale = PriorityScale::load();
+  let mailmap = Mailmap::load();
+  let minRank = final_flags.minPriority.as_ref().and_then(|level| scale.rank(level.as_str()));
+  issues.into_iter().filter(|issue| {
+    matches_body(issue, final_flags, &mailmap, options.as_slice()) &&
+    matches_priority(issue, final_flags, &scale, minRank)
+  }).collect()
+}
+
+///The filters which only need an issue's body: author, status and
+///--open/--closed.
+fn matches_body(issue:&Issue, flags:&Flags, mailmap:&Mailmap, options:&[StatusOption]) -> bool {
+  (flags.authors.len() == 0 || {
+    let author = mailmap.canonical(issue.author());
+    flags.authors.iter().any(|ident| author.matches(ident.as_str()))
+  }) &&
+  (flags.statuses.len() == 0 || flags.statuses.contains(&issue.status.name)) &&
+  matches_category(issue.status.name.as_str(), flags, options)
+}
+
+///Whether listing needs the issues' events (tags, comments, priority
+///and other changes), for a filter, the sort order or what is printed.
+///Without them, only each issue's body is read.  --short and
+///--nocomment print nothing which comes from events.
+fn needs_events(flags:&Flags) -> bool {
+  let sortedByDate = match flags.sort {SortKey::Date => true, _ => false};
+  !sortedByDate || flags.template.is_some() || flags.id.is_some() || flags.tags.len() > 0 ||
+    flags.priorities.len() > 0 || flags.minPriority.is_some() || flags.fields.len() > 0 ||
+    flags.milestone.is_some() || flags.assignees.len() > 0 || flags.blocked || flags.ready ||
+    !(flags.short || flags.noComments)
+}
+
+///Checks whether the issues to list can be found and printed from
+///the cached summaries alone, which is much faster than reading
+///every issue.  That's the case for short listings using only the
+///filters which the summaries have the information for.
+fn summaries_suffice(flags:&Flags) -> bool {
+  let sortedByDate = match flags.sort {SortKey::Date => true, _ => false};
+  flags.short && sortedByDate && flags.template.is_none() &&
+    flags.priorities.len() == 0 && flags.minPriority.is_none() &&
+    flags.fields.len() == 0 && flags.milestone.is_none() &&
+    flags.assignees.len() == 0 && !flags.blocked && !flags.ready
+}
+
+fn filter_summaries(mut summaries:Vec<IssueSummary>, flags:&Flags) -> Vec<IssueSummary> {
+  for id in flags.id.iter() {
+    summaries = selection::find_matching_summaries(id.as_str(), summaries.as_slice());
+  }
+  let mailmap = Mailmap::load();
+  let options = status_storage::read_status_options();
+  summaries.into_iter().filter(|summary| {
+    (flags.tags.len() == 0 || flags.tags.iter().any(|tag| summary.tags.contains(tag))) &&
+    (flags.authors.len() == 0 || {
+      let author = mailmap.canonical(&summary.author);
+      flags.authors.iter().any(|ident| author.matches(ident.as_str()))
+    }) &&
+    (flags.statuses.len() == 0 || flags.statuses.contains(&summary.status)) &&
+    matches_category(summary.status.as_str(), flags, options.as_slice())
+  }).collect()
+}
+
+///Prints summaries the way print_issue prints issues in short mode,
+///newest first.
+fn print_summaries(mut summaries:Vec<IssueSummary>) -> String {
+  summaries.sort_by(|a, b| b.created.0.to_timespec().cmp(&a.created.0.to_timespec()));
+  let mut to_print = String::new();
+  for summary in summaries.iter() {
+    to_print.push_strln("");
+    to_print.push_strln(format!("\x1b[33m{} (Issue ID: {})\x1b[0m", summary.title, summary.short_id));
+  }
+  to_print
+}
+
+enum SortKey{
+  Date,
+  Priority,
+  Field(String)
 }
 
 struct Flags{
@@ -80,63 +289,237 @@ struct Flags{
   statuses: Vec<String>,
   noComments: bool,
   id:Option<String>,
-  tags:Vec<String>
+  tags:Vec<String>,
+  authors:Vec<String>,
+  assignees:Vec<String>,
+  open:bool,
+  closed:bool,
+  priorities:Vec<String>,
+  minPriority:Option<String>,
+  sort:SortKey,
+  fields:Vec<(String, String)>,
+  template:Option<String>,
+  milestone:Option<String>,
+  blocked:bool,
+  ready:bool,
+  raw:bool
 }
 
-fn std_handler(flags:Flags, input:String) -> NextState<Flags,String> {
-  match input.as_str() {
-    "--short" => Continue(Flags{short:true, .. flags}),
-    "-s" => Continue(Flags{short:true, .. flags}),
-    "--status" => ChangeState(get_status, flags),
-    "--nocomment" => Continue(Flags{noComments:true, .. flags}),
-    "--id" => ChangeState(get_id, flags),
-    "--tag" => ChangeState(get_tag, flags),
-    _ => Continue(flags)
+///Filters for --blocked and --ready.  Only issues which are not
+///closed are kept, since closed issues are neither blocked nor ready.
+fn filter_blocked(issues:Vec<Issue>, flags:&Flags) -> Vec<Issue> {
+  let options = status_storage::read_status_options();
+  let keep:Vec<bool> = issues.iter().map(|issue| {
+    let closed = status_storage::category_of(issue.status.name.as_str(),
+                                             options.as_slice()).is_closed();
+    let blocked = links::open_blockers(issue, issues.as_slice(), options.as_slice()).len() > 0;
+    !closed && ((flags.blocked && blocked) || (flags.ready && !blocked))
+  }).collect();
+  issues.into_iter().zip(keep.into_iter()).filter(|&(_, k)| k).map(|(i, _)| i).collect()
+}
+
+fn print_issue_vec(issues:Vec<Issue>, flags:&Flags, mailmap:&Mailmap) -> String{
+  let scale = PriorityScale::load();
+  //reverse because they're sorted in ascending order
+  //and we want descending
+  let mut date_sorted:Vec<Issue> = date_sort::sort_by_time(issues).into_iter().rev().collect();
+  //sorts are stable, so issues with the same sort key stay sorted by date
+  match flags.sort {
+    SortKey::Priority =>
+      date_sorted.sort_by(|a, b| rank_of(b, &scale).cmp(&rank_of(a, &scale))),
+    SortKey::Field(ref name) => {
+      let field = custom_fields::find_field(name.as_str());
+      date_sorted.sort_by(|a, b| {
+        //issues without a value go last
+        match (a.field(name.as_str()), b.field(name.as_str())) {
+          (Some(x), Some(y)) => field.as_ref().map_or(x.cmp(y), |f| f.compare(x, y)),
+          (Some(_), None) => Ordering::Less,
+          (None, Some(_)) => Ordering::Greater,
+          (None, None) => Ordering::Equal
+        }
+      });
+    }
+    SortKey::Date => {}
   }
+  let mut to_print = String::new();
+  for issue in date_sorted.iter() {
+    to_print = match flags.template {
+      Some(ref template) => {
+        to_print.push_strln(render_template(template.as_str(), issue, mailmap));
+        to_print
+      }
+      None => print_issue(issue, flags, mailmap, &scale, to_print)
+    };
+  }
+  to_print
 }
 
-fn get_status(mut flags:Flags, input:String) -> NextState<Flags, String> {
-  flags.statuses.push(input);
-  ChangeState(std_handler, flags)
+fn matches_field(issue:&Issue, name:&str, value:&str) -> bool {
+  match issue.field(name) {
+    //validate so that e.g. dates and users match however they're typed
+    Some(current) => match custom_fields::find_field(name).map(|f| f.validate(value)) {
+      Some(Ok(ref normalized)) => normalized.as_str() == current,
+      _ => value == current
+    },
+    None => false
+  }
 }
 
-fn get_id(mut flags:Flags, input:String) -> NextState<Flags, String> {
-  flags.id = Some(input);
-  ChangeState(std_handler, flags)
+///Fills in an output template for an issue.  Placeholders are
+///written in braces, e.g. `{id}: {title} ({field:component})`.
+///Unknown placeholders are left as they are.
+fn render_template(template:&str, issue:&Issue, mailmap:&Mailmap) -> String {
+  let mut output = String::new();
+  let mut rest = template;
+  while let Some(start) = rest.find('{') {
+    output.push_str(&rest[.. start]);
+    let afterBrace = &rest[start + 1 ..];
+    match afterBrace.find('}') {
+      Some(end) => {
+        let key = &afterBrace[.. end];
+        match template_value(key, issue, mailmap) {
+          Some(value) => output.push_str(value.as_str()),
+          None => {
+            output.push('{');
+            output.push_str(key);
+            output.push('}');
+          }
+        }
+        rest = &afterBrace[end + 1 ..];
+      }
+      None => {
+        output.push('{');
+        rest = afterBrace;
+      }
+    }
+  }
+  output.push_str(rest);
+  output
 }
 
-fn get_tag(mut flags:Flags, input:String) -> NextState<Flags, String> {
-  flags.tags.push(input);
-  ChangeState(std_handler, flags)
+fn template_value(key:&str, issue:&Issue, mailmap:&Mailmap) -> Option<String> {
+  if key.starts_with("field:") {
+    return Some(issue.field(&key["field:".len() ..]).unwrap_or("").to_string());
+  }
+  match key {
+    "id" => Some(issue.id().to_string()),
+    "title" => Some(issue.title().to_string()),
+    "status" => Some(issue.status.name.clone()),
+    "author" => Some(mailmap.canonical(issue.author()).to_string()),
+    "branch" => Some(issue.branch().to_string()),
+    "created" => Some(issue.creation_time().strftime(issue::TIME_FORMAT).unwrap().to_string()),
+    "priority" => Some(issue.priority().unwrap_or("").to_string()),
+    "milestone" => Some(issue.milestone().unwrap_or("").to_string()),
+    "assignee" => Some(issue.assignee().map_or("".to_string(),
+                                               |assignee| mailmap.canonical(assignee).to_string())),
+    "tags" => Some(issue.all_tags().join(", ")),
+    _ => None
+  }
 }
 
-fn print_issue_vec(issues:Vec<Issue>, flags:&Flags) -> String{
-  let date_sorted = date_sort::sort_by_time(issues);
-  let mut to_print = String::new();
-  //reverse because they're sorted in ascending order
-  //and we want descending
-  for issue in date_sorted.iter().rev() {
-    if flags.statuses.len() == 0 ||
-      flags.statuses.contains(&issue.status.name){ 
-      to_print = print_issue(issue, flags, to_print);
+fn matches_category(status:&str, flags:&Flags, options:&[StatusOption]) -> bool {
+  //with both or neither of --open and --closed, everything matches
+  if flags.open == flags.closed {
+    true
+  }else{
+    let closed = status_storage::category_of(status, options).is_closed();
+    closed == flags.closed
+  }
+}
+
+fn rank_of(issue:&Issue, scale:&PriorityScale) -> Option<usize> {
+  issue.priority().and_then(|level| scale.rank(level))
+}
+
+fn matches_priority(issue:&Issue, flags:&Flags, scale:&PriorityScale, minRank:Option<usize>)
+  -> bool {
+  let level = issue.priority().unwrap_or("");
+  let listed = flags.priorities.len() == 0 ||
+               flags.priorities.iter().any(|x| x.as_str() == level);
+  let highEnough = flags.minPriority.is_none() ||
+                   (minRank.is_some() && rank_of(issue, scale) >= minRank);
+  listed && highEnough
+}
+
+///Checks whether a comment is a reply to another comment on the
+///same issue, in which case it is printed under that comment.
+fn is_reply(issue:&Issue, comment:&IssueComment) -> bool {
+  comment.reply_to.as_ref().map_or(false, |id| issue.find_comments(id.as_str())
+                                                    .iter().any(|parent| parent.id == *id))
+}
+
+///Prints a comment followed by its replies, indented by `depth` levels.
+fn print_comment(issue:&Issue, comment:&IssueComment, depth:usize, mailmap:&Mailmap,
+                 raw:bool) -> String {
+  let indent = repeat("  ").take(depth).collect::<String>();
+  let mut output = String::new();
+  output.push_strln(format!("{}\x1b[32m{} on {}\x1b[0m (Comment ID: {})",
+                            indent,
+                            mailmap.canonical(&comment.author),
+                            comment.creation_time.0.strftime(issue::TIME_FORMAT).unwrap(),
+                            comment.id));
+  output.push_strln(format!("{}For branch {}", indent, comment.branch));
+  for origin in comment.copied_from.iter() {
+    output.push_strln(format!("{}Copied from duplicate issue {}", indent, origin.issue_id));
+  }
+  match issue.comment_text(comment) {
+    Some(text) => {
+      if issue.comment_edited(comment) {
+        output.push_strln(format!("{}(edited)", indent));
+      }
+      output.push_strln(markdown::format_text(text, indent.len() + 2, raw));
     }
+    None => output.push_strln(format!("{}  (comment deleted)", indent))
   }
-  to_print
+  output.push_strln("");
+  for evt in issue.events.iter() {
+    match evt {
+      &TimelineComment(ref reply) if reply.reply_to.as_ref() == Some(&comment.id) => {
+        output.push_str(print_comment(issue, reply, depth + 1, mailmap, raw).as_str());
+      }
+      _ => {}
+    }
+  }
+  output
 }
 
-fn print_issue(issue:&Issue, flags:&Flags, mut to_print:String)
+///Prints an issue.  With --nocomment, only what is in the issue's
+///body is printed, so the events don't have to be read.
+fn print_issue(issue:&Issue, flags:&Flags, mailmap:&Mailmap, scale:&PriorityScale,
+               mut to_print:String)
   -> String {
   to_print.push_strln("");
   to_print.push_strln(format!("\x1b[33m{} (Issue ID: {})\x1b[0m",
                               issue.title(), issue.id()));
   if !flags.short {
     to_print.push_strln(format!("Current status: {}", issue.status.name));
+  }
+  if !flags.short && !flags.noComments {
+    match issue.priority() {
+      Some(level) => to_print.push_strln(format!("Priority: {}{}\x1b[0m",
+                                                 scale.color(level), level)),
+      None => to_print.push_strln("Priority: none")
+    }
+    for milestone in issue.milestone().iter() {
+      to_print.push_strln(format!("Milestone: {}", milestone));
+    }
+    for assignee in issue.assignee().iter() {
+      to_print.push_strln(format!("Assigned to: {}", mailmap.canonical(assignee)));
+    }
+    for (name, value) in issue.fields().into_iter() {
+      to_print.push_strln(format!("{}: {}", name, value));
+    }
+    for &(ref link_type, ref target) in issue.links().iter().chain(issue.reverse_links.iter()) {
+      to_print.push_strln(format!("\x1b[35m{} {}\x1b[0m", link_type, target));
+    }
+  }
+  if !flags.short {
     to_print.push_strln(format!("\x1b[34mReported by {} on {}\x1b[0m",
-                       issue.author(), 
+                       mailmap.canonical(issue.author()), 
                        issue.creation_time().strftime(issue::TIME_FORMAT).unwrap()));
     to_print.push_strln(format!("Originated on branch {}\n", issue.branch())); 
     if issue.body_text().len() > 0 {
-      to_print.push_strln(issue.body_text());
+      to_print.push_strln(markdown::format_text(issue.body_text(), 0, flags.raw));
     }
     if !flags.noComments {
       if issue.events.len() == 0 {
@@ -146,15 +529,8 @@ fn print_issue(issue:&Issue, flags:&Flags, mut to_print:String)
         let mut comment_output = String::new();
         for evt in issue.events.iter() {
           match evt {
-            &TimelineComment(ref comment) => {
-              comment_output.push_strln(format!("  \x1b[32m{} on {}\x1b[0m",
-                               comment.author, 
-                               comment.creation_time.0.strftime(issue::TIME_FORMAT).unwrap()));
-              comment_output.push_strln(format!("  For branch {}", comment.branch));
-              for line in comment.body_text.as_str().lines() {
-                comment_output.push_strln(format!("    {}", line));
-              }
-              comment_output.push_strln("");
+            &TimelineComment(ref comment) if !is_reply(issue, comment) => {
+              comment_output.push_str(print_comment(issue, comment, 1, mailmap, flags.raw).as_str());
             }
             _ => {}
           }
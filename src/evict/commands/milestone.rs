@@ -0,0 +1,212 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use commands;
+use commands::ParsedArgs;
+use evict::file_manager;
+use evict::status_storage;
+use evict::status_storage::StatusCategory;
+use evict::milestone_storage;
+use evict::milestone_storage::Milestone;
+use evict::issue::{Issue, IssueMilestone};
+use evict::serdetime;
+use evict::serdetime::SerdeTime;
+use serde_json::Error as SerdeError;
+
+pub fn milestone(args:&ParsedArgs) -> isize {
+  let rest = args.values("args");
+  match args.arg("subcommand") {
+    "create" => create_milestone(args, rest.as_slice()),
+    "list" => list_milestones(rest.as_slice()),
+    "close" => close_milestone(rest.as_slice()),
+    "show" => show_milestone(rest.as_slice()),
+    "assign" => assign_milestone(rest.as_slice()),
+    "unassign" => unassign_milestone(rest.as_slice()),
+    _ => usage()
+  }
+}
+
+fn usage() -> isize {
+  println!("milestone usage:");
+  println!("    evict milestone create <name> [--description <text>] [--due <YYYY-MM-DD>]");
+  println!("    evict milestone list");
+  println!("    evict milestone close <name>");
+  println!("    evict milestone show <name>");
+  println!("    evict milestone assign <issue-id> <name>");
+  println!("    evict milestone unassign <issue-id>");
+  1
+}
+
+fn create_milestone(args:&ParsedArgs, rest:&[&str]) -> isize {
+  if rest.len() != 1 {
+    return usage();
+  }
+  let name = rest[0].to_string();
+  let due = match args.value("--due") {
+    Some(dueText) => match serdetime::parse_date(dueText) {
+      Some(date) => Some(SerdeTime(date)),
+      None => {println!("{} is not a date in the form YYYY-MM-DD", dueText); return 2;}
+    },
+    None => None
+  };
+  let mut milestones = match milestone_storage::read_milestones() {
+    Ok(milestones) => milestones,
+    Err(e) => return unreadable(e)
+  };
+  if let Err(msg) = milestone_storage::add_milestone(&mut milestones,
+                                                     Milestone::new(name, args.value("--description").unwrap_or("").to_string(), due)) {
+    println!("{}", msg);
+    return 3;
+  }
+  match milestone_storage::write_milestones(milestones.as_slice()) {
+    Ok(_) => 0,
+    Err(e) => {println!("{}", e); 4}
+  }
+}
+
+fn list_milestones(args:&[&str]) -> isize {
+  if args.len() != 0 {
+    return usage();
+  }
+  let milestones = match milestone_storage::read_milestones() {
+    Ok(milestones) => milestones,
+    Err(e) => return unreadable(e)
+  };
+  for milestone in milestones.iter() {
+    println!("{} ({}){}", milestone.name, if milestone.closed {"closed"} else {"open"},
+             due_text(milestone));
+  }
+  0
+}
+
+fn due_text(milestone:&Milestone) -> String {
+  match milestone.due_date {
+    Some(ref due) => format!(", due {}{}",
+                             due.0.strftime(serdetime::DATE_FORMAT).unwrap(),
+                             if milestone.is_overdue() {" \x1b[31m[overdue]\x1b[0m"} else {""}),
+    None => "".to_string()
+  }
+}
+
+fn close_milestone(args:&[&str]) -> isize {
+  if args.len() != 1 {
+    return usage();
+  }
+  let mut milestones = match milestone_storage::read_milestones() {
+    Ok(milestones) => milestones,
+    Err(e) => return unreadable(e)
+  };
+  if let Err(msg) = milestone_storage::close_milestone(milestones.as_mut_slice(), args[0]) {
+    println!("{}", msg);
+    return 2;
+  }
+  match milestone_storage::write_milestones(milestones.as_slice()) {
+    Ok(_) => 0,
+    Err(e) => {println!("{}", e); 4}
+  }
+}
+
+fn show_milestone(args:&[&str]) -> isize {
+  if args.len() != 1 {
+    return usage();
+  }
+  let milestone = match milestone_storage::find_milestone(args[0]) {
+    Ok(Some(milestone)) => milestone,
+    Ok(None) => {println!("No milestone named {}", args[0]); return 2;}
+    Err(e) => return unreadable(e)
+  };
+  let options = status_storage::read_status_options();
+  let repo = match commands::repository() {
+    Ok(repo) => repo,
+    Err(e) => {println!("{}", e); return 4;}
+  };
+  let assigned:Vec<Issue> = repo.issues().into_iter()
+                              .filter(|i| i.milestone() == Some(milestone.name.as_str()))
+                              .collect();
+  let count_in = |category:StatusCategory| {
+    assigned.iter()
+            .filter(|i| status_storage::category_of(i.status.name.as_str(),
+                                                    options.as_slice()) == category)
+            .count()
+  };
+  let (open, inProgress, closed) = (count_in(StatusCategory::Open),
+                                    count_in(StatusCategory::InProgress),
+                                    count_in(StatusCategory::Closed));
+
+  println!("\x1b[33m{}\x1b[0m ({}){}", milestone.name,
+           if milestone.closed {"closed"} else {"open"}, due_text(&milestone));
+  if milestone.description.len() > 0 {
+    println!("{}", milestone.description);
+  }
+  println!("");
+  println!("{} issues: {} open, {} in progress, {} closed", assigned.len(), open, inProgress, closed);
+  if assigned.len() > 0 {
+    println!("{}% complete", closed * 100 / assigned.len());
+  }
+  if milestone.is_overdue() && open + inProgress > 0 {
+    println!("");
+    println!("\x1b[31mOverdue open issues:\x1b[0m");
+    for issue in assigned.iter() {
+      if !status_storage::category_of(issue.status.name.as_str(), options.as_slice()).is_closed() {
+        println!("  {} (Issue ID: {})", issue.title(), issue.id());
+      }
+    }
+  }
+  0
+}
+
+fn assign_milestone(args:&[&str]) -> isize {
+  if args.len() != 2 {
+    return usage();
+  }
+  let milestones = match milestone_storage::read_milestones() {
+    Ok(milestones) => milestones,
+    Err(e) => return unreadable(e)
+  };
+  match milestone_storage::assignable_milestone(milestones.as_slice(), args[1]) {
+    Ok(name) => update_milestone(args[0], Some(name)),
+    Err(msg) => {println!("{}", msg); 2}
+  }
+}
+
+fn unassign_milestone(args:&[&str]) -> isize {
+  if args.len() != 1 {
+    return usage();
+  }
+  update_milestone(args[0], None)
+}
+
+///Reports a milestones file which can't be read.  Nothing is written
+///in that case, since it would replace the milestones in the file.
+fn unreadable(e:SerdeError) -> isize {
+  println!("Could not read {}/milestones: {}", file_manager::EVICT_DIRECTORY, e);
+  5
+}
+
+fn update_milestone(idPart:&str, milestone:Option<String>) -> isize {
+  let updated = commands::update_issue(idPart, |mut issue| {
+    if issue.milestone() != milestone.as_ref().map(|x| x.as_str()) {
+      issue.set_milestone(IssueMilestone::new(milestone, commands::get_author()));
+    }
+    issue
+  });
+  match updated {
+    Ok(_) => 0,
+    Err(e) => {println!("{}", e); 4}
+  }
+}
@@ -17,17 +17,24 @@
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
 use std;
-use config;
+use evict::config;
 use std::io::stdout;
 use std::io::stdin;
 use std::io::BufReader;
 use std::io::BufRead;
 use std::io::Write;
+use std::io::Read;
 use std::collections::hash_map::HashMap;
 use std::process;
 
-use file_util;
-use file_manager;
+use evict::file_util;
+use evict::vcs_status;
+use evict::author::Author;
+use evict::repo_lock::RepoLock;
+use evict::repository::{Repository, RepositoryError, RepositoryResult};
+use evict::issue::Issue;
+use evict::file_manager::IssueChange;
+use evict::selection;
 
 mod init;
 mod create;
@@ -39,47 +46,277 @@ mod default_author;
 mod set_status;
 mod default_status;
 mod tag;
+mod statuses;
+mod priority;
+mod milestone;
+mod link;
+mod graph;
+mod duplicate;
+mod set_field;
+mod assign;
+mod show;
+mod help;
+mod completions;
+mod command;
+mod external;
+mod undo;
+mod bulk;
 
-/* A command takes a list of argument strings,
- * performs some action, then returns an
- * exit code.
- */
-pub type Command = fn (Vec<String>) -> isize;
+pub use self::command::{Command, CommandFn, StandardCommand, Arg, ArgCheck, Completion, ParsedArgs};
+
+pub type CommandList = HashMap<String, Box<Command>>;
 
+///Runs `command`, which is a standard command, an alias from the
+///repository's config, or an `evict-<command>` executable on PATH,
+///looked for in that order.
 pub fn execute_command(command:&String,
-                      commandList:&HashMap<String, Command>,
+                      commandList:&CommandList,
                       argList: Vec<String>) -> ! {
-  // [quality] This should be done without hardcoding init as the exception
-  if command != &"init".to_string() &&
-     !file_util::file_exists(file_manager::EVICT_DIRECTORY) {
-    println!("There is no evict directory.  Run evict init.");
-    process::exit(2);
+  if let Some(cmd) = commandList.get(command) {
+    run_command(command, &**cmd, argList);
+  }
+  let repo = std::env::current_dir().map_err(RepositoryError::from)
+                                     .and_then(Repository::discover)
+                                     .ok();
+  let alias = repo.as_ref().and_then(|r| external::expand_alias(command, r, argList.clone()));
+  let (command, argList) = alias.unwrap_or((command.clone(), argList));
+  //aliases can't refer to other aliases, so they can't loop
+  if let Some(cmd) = commandList.get(&command) {
+    run_command(&command, &**cmd, argList);
   }
-  match commandList.get(command) {
-    Some(cmd) => {let exit = (*cmd)(argList); process::exit(exit as i32)}
+  match external::find_external(command.as_str()) {
+    Some(executable) => {
+      let exit = external::run_external(&executable, argList, repo.as_ref());
+      process::exit(exit as i32)
+    }
     None => {
-     println!("Command {} not found", command);
-     process::exit(1);
+      println!("Command {} not found.  Run evict help for a list of commands.", command);
+      process::exit(1);
+    }
+  }
+}
+
+fn run_command(command:&str, cmd:&Command, argList:Vec<String>) -> ! {
+  let parsed = match command::parse_args(cmd.args(), argList.as_slice()) {
+    Ok(ArgCheck::Run(parsed)) => parsed,
+    Ok(ArgCheck::Help) => {
+      command::print_help(cmd);
+      process::exit(0);
+    }
+    Err(msg) => {
+      println!("{}: {}", command, msg);
+      println!("usage: {}", command::usage_line(cmd));
+      process::exit(1);
+    }
+  };
+  if cmd.needs_repository() {
+    //like git, evict works from anywhere inside the repository
+    let found = std::env::current_dir().map_err(RepositoryError::from)
+                                       .and_then(Repository::discover)
+                                       .and_then(|repo| {
+      std::env::set_current_dir(repo.root()).map_err(RepositoryError::from)
+    });
+    if let Err(e) = found {
+      println!("{}", e);
+      process::exit(2);
     }
   }
+  let exit = {
+    //held until the command finishes, since process::exit skips destructors
+    let _lock = if cmd.takes_lock() {
+      match RepoLock::acquire() {
+        Ok(lock) => Some(lock),
+        Err(msg) => {
+          println!("{}", msg);
+          process::exit(3);
+        }
+      }
+    }else{
+      None
+    };
+    cmd.run(&parsed)
+  };
+  process::exit(exit as i32)
+}
+
+static CATEGORIES:[&'static str; 3] = ["open", "in-progress", "closed"];
+static GRAPH_FORMATS:[&'static str; 2] = ["dot", "mermaid"];
+static LINK_TYPES:[&'static str; 4] = ["blocks", "depends-on", "duplicates", "relates-to"];
+static MILESTONE_SUBCOMMANDS:[&'static str; 6] = ["create", "list", "close", "show", "assign",
+                                                  "unassign"];
+
+fn link_command(name:&'static str, summary:&'static str, run:CommandFn) -> StandardCommand {
+  StandardCommand::new(name, summary, run)
+    .arg(Arg::positional("issue-id", "The last digits of the issue's ID")
+           .completes(Completion::Issue))
+    .arg(Arg::positional("link-type", "blocks, depends-on, duplicates or relates-to")
+           .completes(Completion::Values(&LINK_TYPES)))
+    .arg(Arg::positional("target-id", "The last digits of the other issue's ID")
+           .completes(Completion::Issue))
 }
 
-pub fn standard_commands() -> HashMap<String, Command> {
-  let mut hmap:HashMap<String, Command> = HashMap::new();
-  hmap.insert("create".to_string(), create::create_issue);
-  hmap.insert("clear".to_string(), clear::clear_data);
-  hmap.insert("init".to_string(), init::initialize);
-  hmap.insert("list".to_string(), list::list_issues);
-  hmap.insert("comment".to_string(), comment::new_comment);
-  hmap.insert("new-status".to_string(), new_status::new_status);
-  hmap.insert("default-author".to_string(), default_author::default_author);
-  hmap.insert("set-status".to_string(), set_status::set_status);
-  hmap.insert("default-status".to_string(), default_status::default_status);
-  hmap.insert("tag".to_string(), tag::tag);
-  hmap.insert("untag".to_string(), tag::untag);
-  //hmap.insert("parse".to_string(), parse::parse_issues);
+fn add(commands:&mut CommandList, command:StandardCommand) {
+  commands.insert(command.name().to_string(), Box::new(command));
+}
+
+pub fn standard_commands() -> CommandList {
+  let issueId = Arg::positional("issue-id", "The last digits of the issue's ID")
+                  .completes(Completion::Issue);
+  let status = Arg::positional("status", "The name or index of a status")
+                 .completes(Completion::Status);
+  let category = Arg::positional("category", "open, in-progress or closed")
+                   .completes(Completion::Values(&CATEGORIES));
+  let tag = Arg::positional("tag", "The tag").completes(Completion::Tag);
+  //commands taking --where can be given several IDs, or none
+  let issueIds = Arg::positional("issue-id", "The last digits of the issue's ID; separate several with commas")
+                   .optional()
+                   .completes(Completion::Issue);
+  let mut cmds:CommandList = HashMap::new();
+  add(&mut cmds, StandardCommand::new("init", "Create the evict directory here", init::initialize)
+                   .without_repository());
+  add(&mut cmds, StandardCommand::new("clear", "Delete the evict directory and every issue in it",
+                                      clear::clear_data));
+  add(&mut cmds, StandardCommand::new("help", "Describe evict's commands, or one of them",
+                                      help::help)
+                   .arg(Arg::positional("command", "The command to describe").optional()
+                          .completes(Completion::Command))
+                   .without_repository());
+  add(&mut cmds, StandardCommand::new("create", "Create a new issue", create::create_issue)
+                   .arg(Arg::option("--title", "title", "The issue's title"))
+                   .arg(Arg::option("--body", "text", "The issue's body"))
+                   .arg(Arg::option("--body-file", "file", "Read the body from a file, or stdin for -")
+                          .completes(Completion::File))
+                   .arg(Arg::flag("--no-body", "Create the issue with an empty body"))
+                   .arg(Arg::option("--author", "author", "The issue's author"))
+                   .arg(Arg::option("--template", "name", "Start the body from a template"))
+                   .arg(Arg::flag("--no-template", "Don't use the default template"))
+                   .arg(Arg::option("--tag", "tag", "Tag the issue; may be repeated")
+                          .completes(Completion::Tag))
+                   .arg(Arg::option("--status", "status", "The issue's status")
+                          .completes(Completion::Status))
+                   .arg(Arg::option("--assignee", "author", "Assign the issue, or me for yourself"))
+                   .arg(Arg::flag("--no-input", "Fail rather than prompt or open an editor")));
+  add(&mut cmds, StandardCommand::new("list", "List issues, optionally filtered and sorted",
+                                      list::list_issues)
+                   .args(list::list_args()));
+  add(&mut cmds, StandardCommand::new("show", "Show an issue and its timeline", show::show_issue)
+                   .arg(issueId)
+                   .arg(Arg::option("--since", "YYYY-MM-DD", "Leave out earlier events"))
+                   .arg(Arg::flag("--raw", "Print Markdown as written")));
+  add(&mut cmds, StandardCommand::new("graph", "Print the links between issues as a graph",
+                                      graph::graph)
+                   .arg(Arg::option("--format", "dot|mermaid", "The graph format; dot by default")
+                          .completes(Completion::Values(&GRAPH_FORMATS)))
+                   //list's own --format is a template, which a graph has no use for
+                   .args(list::list_args().into_iter().filter(|a| a.name != "--format").collect()));
+  add(&mut cmds, StandardCommand::new("comment", "Comment on an issue, or edit or delete a comment",
+                                      comment::new_comment)
+                   .arg(issueIds)
+                   .arg(Arg::option("--message", "text", "The comment text").alias("-m"))
+                   .arg(Arg::option("--file", "file", "Read the comment from a file, or stdin for -")
+                          .completes(Completion::File))
+                   .arg(Arg::option("--reply-to", "comment-id", "Reply to a comment"))
+                   .arg(Arg::option("--edit", "comment-id", "Edit a comment"))
+                   .arg(Arg::option("--delete", "comment-id", "Delete a comment"))
+                   .args(bulk::bulk_args()));
+  add(&mut cmds, StandardCommand::new("new-status", "Add a status option", new_status::new_status)
+                   .arg(Arg::positional("name", "The new status's name"))
+                   .arg(category.optional())
+                   .with_lock());
+  add(&mut cmds, StandardCommand::new("status-category", "Change the category of a status option",
+                                      new_status::status_category)
+                   .arg(status)
+                   .arg(category)
+                   .with_lock());
+  add(&mut cmds, StandardCommand::new("default-author", "Show or set the default author",
+                                      default_author::default_author)
+                   .arg(Arg::positional("author", "The new default author").optional())
+                   .with_lock());
+  add(&mut cmds, StandardCommand::new("set-status", "Change an issue's status", set_status::set_status)
+                   .arg(issueIds)
+                   .arg(status)
+                   .arg(Arg::flag("--force", "Allow a transition the workflow doesn't"))
+                   .args(bulk::bulk_args()));
+  add(&mut cmds, StandardCommand::new("default-status", "Show or set the status for new issues",
+                                      default_status::default_status)
+                   .arg(status.optional())
+                   .with_lock());
+  add(&mut cmds, StandardCommand::new("tag", "Tag an issue", tag::tag)
+                   .arg(issueIds)
+                   .arg(tag)
+                   .args(bulk::bulk_args()));
+  add(&mut cmds, StandardCommand::new("untag", "Remove a tag from an issue", tag::untag)
+                   .arg(issueIds)
+                   .arg(tag)
+                   .args(bulk::bulk_args()));
+  add(&mut cmds, StandardCommand::new("statuses", "List the status options", statuses::list_statuses));
+  add(&mut cmds, StandardCommand::new("rename-status", "Rename a status option and its issues",
+                                      statuses::rename_status)
+                   .arg(Arg::positional("old-name", "The status to rename"))
+                   .arg(Arg::positional("new-name", "Its new name"))
+                   .with_lock());
+  add(&mut cmds, StandardCommand::new("remove-status", "Remove a status option",
+                                      statuses::remove_status)
+                   .arg(status)
+                   .arg(Arg::option("--to", "status", "Move issues with the status to this one")
+                          .completes(Completion::Status))
+                   .with_lock());
+  add(&mut cmds, StandardCommand::new("move-status", "Move a status option in the list",
+                                      statuses::move_status)
+                   .arg(status)
+                   .arg(Arg::positional("index", "Its new position"))
+                   .with_lock());
+  add(&mut cmds, StandardCommand::new("priority", "Show or set an issue's priority", priority::priority)
+                   .arg(issueId)
+                   .arg(Arg::positional("level", "The name or index of a priority level").optional()));
+  add(&mut cmds, StandardCommand::new("milestone", "Create, list, close, show and assign milestones",
+                                      milestone::milestone)
+                   .arg(Arg::positional("subcommand", "create, list, close, show, assign or unassign")
+                          .completes(Completion::Values(&MILESTONE_SUBCOMMANDS)))
+                   .arg(Arg::rest("args", "The subcommand's arguments"))
+                   .arg(Arg::option("--description", "text", "With create, the description"))
+                   .arg(Arg::option("--due", "YYYY-MM-DD", "With create, the due date"))
+                   .with_lock());
+  add(&mut cmds, link_command("link", "Link an issue to another", link::link));
+  add(&mut cmds, link_command("unlink", "Remove a link between issues", link::unlink));
+  add(&mut cmds, StandardCommand::new("mark-duplicate", "Close an issue as a duplicate of another",
+                                      duplicate::mark_duplicate)
+                   .arg(Arg::positional("duplicate-id", "The duplicate issue")
+                          .completes(Completion::Issue))
+                   .arg(Arg::positional("canonical-id", "The issue it duplicates")
+                          .completes(Completion::Issue)));
+  add(&mut cmds, StandardCommand::new("set", "Set a custom field on an issue", set_field::set_field)
+                   .arg(issueId)
+                   .arg(Arg::positional("field", "The field's name"))
+                   .arg(Arg::positional("value", "Its new value")));
+  add(&mut cmds, StandardCommand::new("unset", "Remove a custom field from an issue",
+                                      set_field::unset_field)
+                   .arg(issueId)
+                   .arg(Arg::positional("field", "The field's name")));
+  add(&mut cmds, StandardCommand::new("assign", "Assign an issue to someone", assign::assign)
+                   .arg(issueIds)
+                   .arg(Arg::positional("assignee", "Who to assign it to, or me"))
+                   .args(bulk::bulk_args()));
+  add(&mut cmds, StandardCommand::new("unassign", "Unassign an issue", assign::unassign)
+                   .arg(issueIds)
+                   .args(bulk::bulk_args()));
+  add(&mut cmds, StandardCommand::new("undo", "Undo the last changes made to issues", undo::undo)
+                   .arg(Arg::positional("count", "How many operations to undo; 1 by default").optional())
+                   .arg(Arg::flag("--list", "List the operations which can be undone"))
+                   .with_lock());
+  add(&mut cmds, StandardCommand::new("completions", "Print a shell completion script",
+                                      completions::completions)
+                   .arg(Arg::positional("shell", "bash, zsh or fish")
+                          .completes(Completion::Values(&completions::SHELLS)))
+                   .without_repository());
+  add(&mut cmds, StandardCommand::new("__complete", "List completions of a kind for a shell",
+                                      completions::complete)
+                   .arg(Arg::positional("kind", "issues, statuses, tags or commands"))
+                   .without_repository()
+                   .hide());
+  //cmds.insert("parse".to_string(), parse::parse_issues);
 
-  hmap
+  cmds
 }
 
 pub fn prompt(prompt:&str) -> String{
@@ -91,12 +328,90 @@ pub fn prompt(prompt:&str) -> String{
   withNewline.replace("\n", "").replace("\r", "")
 }
 
-pub fn get_author() -> String {
+///Finds the author for a new issue, comment or tag.  The default
+///author from the repo config takes priority, then the user name and
+///email configured in the VCS.  Only prompts if neither is set.
+pub fn get_author() -> Author {
+  match find_author() {
+    Some(author) => author,
+    None => Author::parse(prompt("Author: ").as_str())
+  }
+}
+
+///Like get_author, but returns None instead of prompting.
+pub fn find_author() -> Option<Author> {
   let config = config::Config::load();
   match config.author {
-    Some(author) => author,
-    None => prompt("Author: ")
+    Some(author) => Some(Author::parse(author.as_str())),
+    None => vcs_status::user_name().map(|name| Author::new(name, vcs_status::user_email()))
+  }
+}
+
+///The repository commands run in.  run_command has already changed
+///to its root.
+pub fn repository() -> RepositoryResult<Repository> {
+  Repository::open(".")
+}
+
+///Repository::find, noting on stderr when `idPart` named a duplicate
+///and the issue it duplicates was found instead.
+pub fn find_issue(repo:&Repository, idPart:&str) -> RepositoryResult<Issue> {
+  let issue = try!(repo.find(idPart));
+  if !selection::id_matches(issue.id(), idPart) {
+    //stderr, so this doesn't end up in output meant for other tools
+    let _ = writeln!(std::io::stderr(), "{} is a duplicate of {}, using {}",
+                     idPart, issue.id(), issue.id());
+  }
+  Ok(issue)
+}
+
+///Repository::save, then prints any warnings, such as failed post
+///hooks, on stderr.
+pub fn save(repo:&Repository, changes:&[IssueChange]) -> RepositoryResult<()> {
+  let saved = repo.save(changes);
+  for warning in repo.take_warnings().into_iter() {
+    let _ = writeln!(std::io::stderr(), "{}", warning);
   }
+  saved
+}
+
+///Saves changes to issues in the current repository, see save.
+pub fn save_changes(changes:&[IssueChange]) -> RepositoryResult<()> {
+  save(&try!(repository()), changes)
+}
+
+///Reads the issue matching `idPart`, applies `update` to it and saves
+///the change, returning the updated issue.  Only that issue is read,
+///since its ID is resolved through the summary cache.  The lock is
+///only taken to save, so `update` may prompt.
+pub fn update_issue<F:FnOnce(Issue) -> Issue>(idPart:&str, update:F) -> RepositoryResult<Issue> {
+  let repo = try!(repository());
+  let changes = vec!(IssueChange::apply(try!(find_issue(&repo, idPart)), update));
+  try!(save(&repo, changes.as_slice()));
+  Ok(changes.into_iter().next().unwrap().after)
+}
+
+///Reads all of stdin, for commands given `-` in place of text.
+pub fn read_stdin() -> Option<String> {
+  let mut text = String::new();
+  stdin().read_to_string(&mut text).ok().map(|_| text)
+}
+
+///Opens the editor on a private temporary file containing `initial`,
+///and returns the saved text.  Returns None if the editor couldn't be
+///run or failed.  `name` is used in the temporary file's name.
+pub fn edit_text(name:&str, initial:&str) -> Option<String> {
+  let path = match file_util::create_private_temp_file(name, initial) {
+    Ok(path) => path,
+    Err(e) => {
+      println!("Couldn't create a temporary file: {}", e);
+      return None;
+    }
+  };
+  let edited = edit_file(&path.to_string_lossy());
+  let text = if edited {file_util::read_string_from_path(&path).ok()} else {None};
+  let _ = std::fs::remove_file(&path);
+  text
 }
 
 pub fn edit_file(filename:&str) -> bool{
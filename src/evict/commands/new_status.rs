@@ -17,19 +17,38 @@
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use status_storage;
+use evict::status_storage;
+use evict::status_storage::{StatusOption, StatusCategory};
+use commands::ParsedArgs;
 
-pub fn new_status(mut args:Vec<String>) -> isize {
-  if args.len() != 1 {
-    println!("new-status usage: evict new-status <status-name>");
-    1
-  }else{
-    let mut newStatuses = status_storage::read_status_options();
+pub fn new_status(args:&ParsedArgs) -> isize {
+  let name = args.arg("name");
+  let category = match args.value("category") {
+    Some(category) => StatusCategory::from_str(category),
+    None => Some(StatusOption::new(name.to_string()).category)
+  };
+  match category {
+    Some(category) =>
+      match status_storage::add_status_option(name, category) {
+        Ok(_) => 0,
+        Err(e) => {println!("{}", e); 2}
+      },
+    None => {
+      println!("new-status usage: evict new-status <status-name> [open|in-progress|closed]");
+      1
+    }
+  }
+}
 
-    //need a better way to move a value out of a vec
-    newStatuses.push(status_storage::StatusOption{name:args.swap_remove(0)});
-    //TODO check this
-    let _ = status_storage::write_status_options(newStatuses);
-    0
+pub fn status_category(args:&ParsedArgs) -> isize {
+  match StatusCategory::from_str(args.arg("category")) {
+    Some(category) => match status_storage::set_status_category(args.arg("status"), category) {
+      Ok(_) => 0,
+      Err(e) => {println!("{}", e); 2}
+    },
+    None => {
+      println!("status-category usage: evict status-category <status-name> <open|in-progress|closed>");
+      1
+    }
   }
 }
@@ -0,0 +1,71 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use evict::issue::{IssuePriority, Issue};
+use commands;
+use commands::ParsedArgs;
+use evict::priority::PriorityScale;
+
+pub fn priority(args:&ParsedArgs) -> isize {
+  let idPart = args.arg("issue-id");
+  let levelArg = match args.value("level") {
+    Some(level) => level,
+    None => return print_priority(idPart)
+  };
+  let scale = PriorityScale::load();
+  match scale.resolve(levelArg) {
+    Some(level) => {
+      match commands::update_issue(idPart, |issue| modify_priority(issue, level)) {
+        Ok(_) => 0,
+        Err(e) => {
+          println!("{}", e);
+          2
+        }
+      }
+    }
+    None => {
+      println!("{} is not a priority level.  Levels are: {}", levelArg, scale.levels().join(", "));
+      3
+    }
+  }
+}
+
+fn print_priority(idPart:&str) -> isize {
+  let found = commands::repository().and_then(|repo| commands::find_issue(&repo, idPart));
+  match found {
+    Ok(issue) => {
+      match issue.priority() {
+        Some(level) => println!("{}", level),
+        None => println!("No priority set")
+      }
+      0
+    }
+    Err(e) => {
+      println!("{}", e);
+      1
+    }
+  }
+}
+
+fn modify_priority(mut issue:Issue, level:&str) -> Issue {
+  if issue.priority() != Some(level) {
+    let author = commands::get_author();
+    issue.set_priority(IssuePriority::new(level.to_string(), author));
+  }
+  issue
+}
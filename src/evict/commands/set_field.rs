@@ -0,0 +1,70 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use evict::issue::{IssueField, Issue};
+use commands;
+use commands::ParsedArgs;
+use evict::custom_fields;
+use evict::custom_fields::CustomField;
+
+pub fn set_field(args:&ParsedArgs) -> isize {
+  let field = match defined_field(args.arg("field")) {
+    Some(field) => field,
+    None => return 2
+  };
+  match field.validate(args.arg("value")) {
+    Ok(value) => update_field(args.arg("issue-id"), &field, Some(value)),
+    Err(msg) => {println!("{}", msg); 3}
+  }
+}
+
+pub fn unset_field(args:&ParsedArgs) -> isize {
+  match defined_field(args.arg("field")) {
+    Some(field) => update_field(args.arg("issue-id"), &field, None),
+    None => 2
+  }
+}
+
+fn defined_field(name:&str) -> Option<CustomField> {
+  let field = custom_fields::find_field(name);
+  if field.is_none() {
+    let names:Vec<String> = custom_fields::read_field_definitions().into_iter()
+                                                                   .map(|f| f.name)
+                                                                   .collect();
+    println!("{} is not a custom field.  Defined fields are: {}", name, names.join(", "));
+  }
+  field
+}
+
+fn update_field(idPart:&str, field:&CustomField, value:Option<String>) -> isize {
+  match commands::update_issue(idPart, |issue| modify_field(issue, field, value)) {
+    Ok(_) => 0,
+    Err(e) => {
+      println!("{}", e);
+      4
+    }
+  }
+}
+
+fn modify_field(mut issue:Issue, field:&CustomField, value:Option<String>) -> Issue {
+  if issue.field(field.name.as_str()) != value.as_ref().map(|x| x.as_str()) {
+    let author = commands::get_author();
+    issue.set_field(IssueField::new(field.name.clone(), value, author));
+  }
+  issue
+}
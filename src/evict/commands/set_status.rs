@@ -16,45 +16,96 @@
  *   You should have received a copy of the GNU General Public License
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
-use selection;
+use evict::file_manager::IssueChange;
+use commands;
+use commands::bulk;
+use commands::ParsedArgs;
+use evict::status_storage;
+use evict::issue::{Issue, IssueStatus};
 
-use file_manager;
-use status_storage;
-use issue::IssueStatus;
-
-pub fn set_status(args:Vec<String>) -> isize {
-  if args.len() != 2 {
-    println!("set-status usage: evict set-status <issue-id> <status>");
-    println!("    Where <status> is either the full name of a status");
-    println!("    or the index of a status");
-    1
-  }else{
-    match resolve_new_status(args[1].as_str()) {
-      Some(newStatus) => {
-        let issues = file_manager::read_issues();
-        let edited = selection::update_issue(args[0].as_str(), issues, |mut oldIssue| {
-          oldIssue.status = newStatus.clone();
-          oldIssue
-        });
-        //TODO handle this
-        let _ = file_manager::write_issues(edited.as_slice());
-        0
+pub fn set_status(args:&ParsedArgs) -> isize {
+  let force = args.has("--force");
+  let bulkFlags = bulk::flags(args);
+  let ids = args.value("issue-id");
+  let status = match args.value("status") {
+    Some(status) if ids.is_some() || bulkFlags.query.is_some() => status,
+    _ => {
+      println!("set-status usage: evict set-status [--force] <issue-id>[,<issue-id>...] <status>");
+      println!("                  evict set-status [--force] --where <query> [--yes] <status>");
+      println!("    Where <status> is either the full name of a status");
+      println!("    or the index of a status.  --force allows status changes");
+      println!("    which the configured transitions don't permit.");
+      return 1;
+    }
+  };
+  match resolve_new_status(status) {
+    Some(newStatus) => {
+      if bulk::is_bulk(&bulkFlags, ids) {
+        return set_status_bulk(&bulkFlags, ids, newStatus, force);
       }
-      None => {println!("Given status does not exist"); 2}
+      set_status_single(ids.unwrap_or(""), newStatus, force)
     }
+    None => {println!("Given status does not exist"); 2}
   }
 }
 
-fn resolve_new_status(statusIdent:&str) -> Option<IssueStatus> {
-  let search = status_storage::read_status_options();
-  match usize::from_str_radix(statusIdent, 10) {
-    Ok(index) =>
-      if search.len() > index {
-        Some(search[index].clone())
-      } else {
-        None
-      },
-    _ => search.into_iter().find(|x| x.name.as_str() == statusIdent)
-  }.map(|x| x.make_status())
+fn set_status_single(idPart:&str, newStatus:IssueStatus, force:bool) -> isize {
+  let repo = match commands::repository() {
+    Ok(repo) => repo,
+    Err(e) => {println!("{}", e); return 4}
+  };
+  let issue = match commands::find_issue(&repo, idPart) {
+    Ok(issue) => issue,
+    Err(e) => {println!("{}", e); return 4}
+  };
+  if !force && !status_storage::transition_allowed(issue.status.name.as_str(),
+                                                   newStatus.name.as_str()) {
+    println!("Can't change status from {} to {}.  Use --force to override.",
+             issue.status.name, newStatus.name);
+    return 3;
+  }
+  let author = commands::get_author();
+  let change = IssueChange::apply(issue, |mut oldIssue| {
+    oldIssue.change_status(newStatus, author);
+    oldIssue
+  });
+  match commands::save(&repo, &[change]) {
+    Ok(_) => 0,
+    Err(e) => {
+      println!("{}", e);
+      4
+    }
+  }
 }
 
+///Changes the status of every selected issue.  Issues whose status
+///can't move to `newStatus` are left out unless `force` is set.
+fn set_status_bulk(bulkFlags:&bulk::BulkFlags, ids:Option<&str>, newStatus:IssueStatus,
+                   force:bool) -> isize {
+  let selected = match bulk::select_issues(bulkFlags, ids) {
+    Ok(selected) => selected,
+    Err(e) => {
+      println!("{}", e);
+      return 3;
+    }
+  };
+  let (allowed, refused):(Vec<Issue>, Vec<Issue>) = selected.into_iter().partition(|issue| {
+    force || status_storage::transition_allowed(issue.status.name.as_str(), newStatus.name.as_str())
+  });
+  for issue in refused.iter() {
+    println!("Skipping {}: can't change status from {} to {}.  Use --force to override.",
+             issue.id(), issue.status.name, newStatus.name);
+  }
+  let author = commands::get_author();
+  let action = format!("Set status to {} on", newStatus.name);
+  bulk::run_confirmed(bulkFlags, action.as_str(), allowed, |mut issue| {
+    if issue.status.name != newStatus.name {
+      issue.change_status(newStatus.clone(), author.clone());
+    }
+    issue
+  })
+}
+
+fn resolve_new_status(statusIdent:&str) -> Option<IssueStatus> {
+  status_storage::find_status_option(statusIdent).map(|x| x.make_status())
+}
@@ -0,0 +1,193 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use evict::issue;
+use evict::issue::{Issue, IssueTimelineEvent};
+use evict::issue::IssueTimelineEvent::*;
+use evict::author::Mailmap;
+use evict::serdetime;
+use commands;
+use commands::ParsedArgs;
+use evict::markdown;
+
+use time;
+
+pub fn show_issue(args:&ParsedArgs) -> isize {
+  let since = match args.value("--since") {
+    Some(text) => match serdetime::parse_date(text) {
+      Some(date) => Some(date),
+      None => {
+        println!("{} is not a date in the form YYYY-MM-DD", text);
+        return 2;
+      }
+    },
+    None => None
+  };
+  let idPart = args.arg("issue-id");
+
+  //only the issue being shown is read; the cache is enough to find it and its reverse links
+  let found = commands::repository().and_then(|repo| commands::find_issue(&repo, idPart));
+  match found {
+    Ok(issue) => {
+      println!("{}", render_issue(&issue, since.as_ref(), args.has("--raw"), &Mailmap::load()));
+      0
+    }
+    Err(e) => {
+      println!("{}", e);
+      3
+    }
+  }
+}
+
+///Renders the issue details followed by every event in its
+///timeline, oldest first.  Events before `since` are left out.
+fn render_issue(issue:&Issue, since:Option<&time::Tm>, raw:bool, mailmap:&Mailmap) -> String {
+  let now = serdetime::stored_now();
+  let mut output = String::new();
+  output.push_str(format!("\x1b[33m{} (Issue ID: {})\x1b[0m\n", issue.title(), issue.id()).as_str());
+  output.push_str(format!("Status: {}\n", issue.status.name).as_str());
+  for level in issue.priority().iter() {
+    output.push_str(format!("Priority: {}\n", level).as_str());
+  }
+  for milestone in issue.milestone().iter() {
+    output.push_str(format!("Milestone: {}\n", milestone).as_str());
+  }
+  for assignee in issue.assignee().iter() {
+    output.push_str(format!("Assigned to: {}\n", mailmap.canonical(assignee)).as_str());
+  }
+  for (name, value) in issue.fields().into_iter() {
+    output.push_str(format!("{}: {}\n", name, value).as_str());
+  }
+  let tags = issue.all_tags();
+  if tags.len() > 0 {
+    output.push_str(format!("Tags: {}\n", tags.join(", ")).as_str());
+  }
+  for &(ref link_type, ref target) in issue.links().iter().chain(issue.reverse_links.iter()) {
+    output.push_str(format!("\x1b[35m{} {}\x1b[0m\n", link_type, target).as_str());
+  }
+  output.push_str(format!("\x1b[34mReported by {} {} ({})\x1b[0m on branch {}\n\n",
+                          mailmap.canonical(issue.author()),
+                          serdetime::relative_time(&issue.creation_time(), &now),
+                          issue.creation_time().strftime(issue::TIME_FORMAT).unwrap(),
+                          issue.branch()).as_str());
+  if issue.body_text().len() > 0 {
+    output.push_str(markdown::format_text(issue.body_text(), 0, raw).as_str());
+    output.push_str("\n\n");
+  }
+
+  let mut shown:Vec<&IssueTimelineEvent> = issue.events.iter().filter(|evt| {
+    since.map_or(true, |date| evt.time().to_timespec() >= date.to_timespec())
+  }).collect();
+  //issues built in memory may have their events in any order
+  shown.sort_by_key(|evt| evt.time().to_timespec());
+  if shown.len() == 0 {
+    output.push_str("No activity");
+    output.push_str(if since.is_some() {" in this period\n"} else {"\n"});
+  }
+  for evt in shown.into_iter() {
+    let who = mailmap.canonical(evt.author());
+    output.push_str(format!("\x1b[32m{} ({})\x1b[0m {} {}\n",
+                            evt.time().strftime(issue::TIME_FORMAT).unwrap(),
+                            serdetime::relative_time(evt.time(), &now),
+                            who,
+                            describe_event(evt)).as_str());
+    if let &TimelineComment(ref comment) = evt {
+      let text = issue.comment_text(comment).unwrap_or("(comment deleted)");
+      output.push_str(markdown::format_text(text, 4, raw).as_str());
+      output.push_str("\n");
+    }
+  }
+  output
+}
+
+///A short description of what an event did, following the
+///name of whoever did it.
+fn describe_event(evt:&IssueTimelineEvent) -> String {
+  match evt {
+    &TimelineComment(ref comment) => match comment.reply_to {
+      Some(ref parent) => format!("replied to comment {} (Comment ID: {})", parent, comment.id),
+      None => format!("commented (Comment ID: {})", comment.id)
+    },
+    &TimelineTag(ref tag) =>
+      format!("{} tag {}", if tag.enabled {"added"} else {"removed"}, tag.tag_name),
+    &TimelinePriority(ref priority) => format!("set priority to {}", priority.priority),
+    &TimelineMilestone(ref assigned) => match assigned.milestone {
+      Some(ref milestone) => format!("assigned to milestone {}", milestone),
+      None => "removed from its milestone".to_string()
+    },
+    &TimelineLink(ref link) =>
+      format!("{} {} {}", if link.enabled {"linked"} else {"unlinked"}, link.link_type, link.target),
+    &TimelineField(ref field) => match field.value {
+      Some(ref value) => format!("set {} to {}", field.field, value),
+      None => format!("cleared {}", field.field)
+    },
+    &TimelineAssign(ref assignment) => match assignment.assignee {
+      Some(ref assignee) => format!("assigned to {}", assignee),
+      None => "unassigned".to_string()
+    },
+    &TimelineCommentEdit(ref edit) => match edit.body_text {
+      Some(_) => format!("edited comment {}", edit.comment_id),
+      None => format!("deleted comment {}", edit.comment_id)
+    },
+    &TimelineStatus(ref change) => format!("changed status from {} to {}", change.from, change.to)
+  }
+}
+
+///An issue tagged `later` and then, a day earlier, `earlier`, with
+///the events stored in that order.
+#[cfg(test)]
+fn out_of_order_issue() -> Issue {
+  use evict::author::Author;
+  use evict::issue::{IssueTag, SerdeTime};
+
+  let mut issue = Issue::new("A".to_string(), "B".to_string(), Author::parse("C"));
+  for &(name, day) in [("later", 2), ("earlier", 1)].iter() {
+    let mut tag = IssueTag::new(name.to_string(), Author::parse("C"), true);
+    tag.time = SerdeTime(time::at_utc(time::Timespec::new(1400000000 + day * 86400, 0)));
+    issue.add_tag(tag);
+  }
+  issue
+}
+
+#[test]
+pub fn timeline_is_oldest_first(){
+  let output = render_issue(&out_of_order_issue(), None, true, &Mailmap::parse(""));
+  let earlier = output.find("added tag earlier").unwrap();
+  let later = output.find("added tag later").unwrap();
+  assert!(earlier < later);
+}
+
+#[test]
+pub fn since_leaves_out_older_events(){
+  let since = time::at_utc(time::Timespec::new(1400000000 + 2 * 86400, 0));
+  let output = render_issue(&out_of_order_issue(), Some(&since), true, &Mailmap::parse(""));
+  assert!(output.contains("added tag later"));
+  assert!(!output.contains("added tag earlier"));
+  assert!(!output.contains("No activity"));
+}
+
+#[test]
+pub fn no_activity_is_reported(){
+  use evict::author::Author;
+
+  let quiet = Issue::new("A".to_string(), "B".to_string(), Author::parse("C"));
+  assert!(render_issue(&quiet, None, true, &Mailmap::parse("")).ends_with("No activity\n"));
+  let since = time::at_utc(time::Timespec::new(1400000000 + 3 * 86400, 0));
+  let output = render_issue(&out_of_order_issue(), Some(&since), true, &Mailmap::parse(""));
+  assert!(output.ends_with("No activity in this period\n"));
+}
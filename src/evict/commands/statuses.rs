@@ -0,0 +1,257 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use evict::repository::Repository;
+use evict::file_manager::{IssueChange, ReadParts};
+use commands;
+use commands::ParsedArgs;
+use evict::status_storage;
+use evict::status_storage::StatusOption;
+use evict::issue::Issue;
+use evict::author::Author;
+
+pub fn list_statuses(_:&ParsedArgs) -> isize {
+  let repo = match commands::repository() {
+    Ok(repo) => repo,
+    Err(e) => {println!("{}", e); return 2;}
+  };
+  //statuses are kept in the issue bodies, so the events don't need to be read
+  let issues = repo.query(ReadParts::BodyOnly, |_| true);
+  let options = status_storage::read_status_options();
+  let default = status_storage::read_default_status();
+  for (index, option) in options.iter().enumerate() {
+    let count = count_using(option.name.as_str(), issues.as_slice());
+    let defaultMarker = if *option == default {" [default]"} else {""};
+    println!("{}: {} [{}] ({} issue{}){}", index, option.name, option.category.as_str(),
+             count, if count == 1 {""} else {"s"}, defaultMarker);
+  }
+  let unknown = issues.iter()
+                      .filter(|i| !options.iter().any(|o| o.name == i.status.name))
+                      .count();
+  if unknown > 0 {
+    println!("{} issue{} with a status not in this list", unknown,
+             if unknown == 1 {" has"} else {"s have"});
+  }
+  0
+}
+
+pub fn rename_status(args:&ParsedArgs) -> isize {
+  let (oldName, newName) = (args.arg("old-name"), args.arg("new-name"));
+  let repo = match commands::repository() {
+    Ok(repo) => repo,
+    Err(e) => {println!("{}", e); return 3;}
+  };
+  match rename_in(&repo, oldName, newName) {
+    Ok(count) => {
+      println!("Renamed {} to {} ({} issues updated)", oldName, newName, count);
+      0
+    }
+    Err(code) => code
+  }
+}
+
+///Renames a status and every issue which has it, returning how many
+///issues were renamed.  The issues are saved before the status list,
+///so if saving fails (a conflict, a refused hook or an IO error) the
+///status is left as it was.
+fn rename_in(repo:&Repository, oldName:&str, newName:&str) -> Result<usize, isize> {
+  let evictDir = repo.evict_dir();
+  if let Err(e) = status_storage::check_rename_in(evictDir.as_path(), oldName, newName) {
+    println!("{}", e);
+    return Err(2);
+  }
+  //A rename isn't a status change, so the change time stays as it was
+  let renamed:Vec<IssueChange> = repo.reader(ReadParts::BodyOnly).filter_map(|issue| {
+    if issue.status.name.as_str() == oldName {
+      Some(IssueChange::apply(issue, |mut issue| {
+        issue.status.name = newName.to_string();
+        issue
+      }))
+    }else{
+      None
+    }
+  }).collect();
+  if let Err(e) = commands::save(repo, renamed.as_slice()) {
+    println!("{}", e);
+    return Err(3);
+  }
+  match status_storage::rename_status_option_in(evictDir.as_path(), oldName, newName) {
+    Ok(_) => Ok(renamed.len()),
+    Err(e) => {println!("{}", e); Err(2)}
+  }
+}
+
+pub fn remove_status(args:&ParsedArgs) -> isize {
+  let repo = match commands::repository() {
+    Ok(repo) => repo,
+    Err(e) => {println!("{}", e); return 4;}
+  };
+  remove_in(&repo, args.arg("status"), args.value("--to"), commands::get_author)
+}
+
+///Removes a status, first moving the issues which have it to the
+///status `to`, if given.  Like rename_in, the issues are saved before
+///the status list, so a failed save leaves the status in place.
+fn remove_in<A:FnOnce() -> Author>(repo:&Repository, name:&str, to:Option<&str>, author:A) -> isize {
+  let evictDir = repo.evict_dir();
+  let migrateTo = match to {
+    Some(to) => match status_storage::find_status_option_in(evictDir.as_path(), to) {
+      Some(ref target) if target.name.as_str() == name => {
+        println!("Can't migrate issues from {} to itself", name);
+        return 2;
+      }
+      Some(target) => Some(target),
+      None => {println!("{} is not a valid status option", to); return 2;}
+    },
+    None => None
+  };
+  if let Err(e) = status_storage::check_remove_in(evictDir.as_path(), name) {
+    println!("{}", e);
+    return 2;
+  }
+  if migrateTo.is_none() {
+    //stop at the first issue using it, rather than reading them all
+    let inUse = repo.reader(ReadParts::BodyOnly).any(|i| i.status.name.as_str() == name);
+    if inUse {
+      println!("Issues still have status {}.  Use --to <new-status> to migrate them.", name);
+      return 3;
+    }
+  }
+  if let Some(ref target) = migrateTo {
+    let using = repo.query(ReadParts::BodyOnly, |i| i.status.name.as_str() == name);
+    if using.len() > 0 {
+      if let Err(code) = migrate_issues(repo, using, target, author()) {
+        return code;
+      }
+    }
+  }
+  match status_storage::remove_status_option_in(evictDir.as_path(), name) {
+    Ok(_) => 0,
+    Err(e) => {println!("{}", e); 2}
+  }
+}
+
+fn migrate_issues(repo:&Repository, issues:Vec<Issue>, target:&StatusOption, author:Author)
+    -> Result<(), isize> {
+  let count = issues.len();
+  let migrated:Vec<IssueChange> = issues.into_iter().map(|issue| {
+    IssueChange::apply(issue, |mut issue| {
+      issue.change_status(target.make_status(), author.clone());
+      issue
+    })
+  }).collect();
+  match commands::save(repo, migrated.as_slice()) {
+    Ok(_) => {println!("Moved {} issues to {}", count, target.name); Ok(())}
+    Err(e) => {println!("{}", e); Err(4)}
+  }
+}
+
+pub fn move_status(args:&ParsedArgs) -> isize {
+  match usize::from_str_radix(args.arg("index"), 10) {
+    Ok(index) => match status_storage::move_status_option(args.arg("status"), index) {
+      Ok(_) => 0,
+      Err(e) => {println!("{}", e); 2}
+    },
+    Err(_) => {
+      println!("move-status usage: evict move-status <status-name> <new-index>");
+      1
+    }
+  }
+}
+
+fn count_using(name:&str, issues:&[Issue]) -> usize {
+  issues.iter().filter(|i| i.status.name.as_str() == name).count()
+}
+
+///A repository in a new temporary directory with the statuses open,
+///review and closed, and two issues in review.
+#[cfg(test)]
+fn review_repo() -> Repository {
+  use evict::file_manager;
+  use std::env;
+  use std::fs;
+
+  let root = env::temp_dir().join(format!("evict-statuses-test-{}", ::time::precise_time_ns()));
+  let evictDir = root.join(file_manager::EVICT_DIRECTORY);
+  fs::create_dir_all(&evictDir).unwrap();
+  let options = ["open", "review", "closed"].iter().map(|name| StatusOption::new(name.to_string())).collect();
+  status_storage::write_status_options_in(evictDir.as_path(), options).unwrap();
+  status_storage::write_default_status_in(evictDir.as_path(), &StatusOption::new("open".to_string())).unwrap();
+  let repo = Repository::open(&root).unwrap();
+  let created:Vec<IssueChange> = (0 .. 2).map(|_| {
+    let mut issue = Issue::new("A".to_string(), "B".to_string(), Author::parse("C"));
+    issue.status.name = "review".to_string();
+    IssueChange::created(issue)
+  }).collect();
+  repo.save(created.as_slice()).unwrap();
+  repo
+}
+
+#[cfg(test)]
+fn status_names(repo:&Repository) -> Vec<String> {
+  status_storage::read_status_options_in(repo.evict_dir().as_path()).into_iter().map(|o| o.name).collect()
+}
+
+#[cfg(test)]
+fn issue_statuses(repo:&Repository) -> Vec<String> {
+  repo.issues().into_iter().map(|issue| issue.status.name).collect()
+}
+
+#[test]
+pub fn rename_status_renames_issues(){
+  let repo = review_repo();
+  assert_eq!(rename_in(&repo, "review", "closed"), Err(2));
+  assert_eq!(issue_statuses(&repo), vec!("review", "review"));
+
+  assert_eq!(rename_in(&repo, "review", "in-review"), Ok(2));
+  assert_eq!(status_names(&repo), vec!("open", "in-review", "closed"));
+  assert_eq!(issue_statuses(&repo), vec!("in-review", "in-review"));
+  let _ = ::std::fs::remove_dir_all(repo.root());
+}
+
+#[test]
+pub fn remove_status_with_to_moves_issues(){
+  let repo = review_repo();
+  assert_eq!(remove_in(&repo, "review", None, || Author::parse("C")), 3);
+  assert_eq!(status_names(&repo), vec!("open", "review", "closed"));
+
+  assert_eq!(remove_in(&repo, "review", Some("closed"), || Author::parse("C")), 0);
+  assert_eq!(status_names(&repo), vec!("open", "closed"));
+  assert_eq!(issue_statuses(&repo), vec!("closed", "closed"));
+  let _ = ::std::fs::remove_dir_all(repo.root());
+}
+
+#[cfg(unix)]
+#[test]
+pub fn refused_migration_keeps_the_status(){
+  use evict::file_util;
+  use std::fs;
+  use std::os::unix::fs::PermissionsExt;
+
+  let repo = review_repo();
+  let hooks = repo.evict_dir().join("hooks");
+  fs::create_dir_all(&hooks).unwrap();
+  let hook = hooks.join("pre-status-change");
+  file_util::write_string_to_file("#!/bin/sh\nexit 1\n", hook.to_string_lossy().as_ref(), true).unwrap();
+  fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+
+  assert_eq!(remove_in(&repo, "review", Some("closed"), || Author::parse("C")), 4);
+  assert_eq!(status_names(&repo), vec!("open", "review", "closed"));
+  assert_eq!(issue_statuses(&repo), vec!("review", "review"));
+  let _ = fs::remove_dir_all(repo.root());
+}
@@ -16,37 +16,48 @@
  *   You should have received a copy of the GNU General Public License
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
-use selection;
-use issue::{IssueTag, Issue};
+use evict::issue::{IssueTag, Issue};
+use evict::author::Author;
 use commands;
-use file_manager;
+use commands::bulk;
+use commands::ParsedArgs;
 
-pub fn tag(args:Vec<String>) -> isize {
+pub fn tag(args:&ParsedArgs) -> isize {
   tag_cmd(args, "tag", true)
 }
 
-pub fn untag(args:Vec<String>) -> isize {
+pub fn untag(args:&ParsedArgs) -> isize {
   tag_cmd(args, "untag", false)
 }
 
-pub fn tag_cmd(args:Vec<String>, cmdName:&str, enabledAfter:bool) -> isize {
-  if args.len() != 2 {
-    println!("{} usage: evict {} <issue-id> <tag>", cmdName, cmdName);
-    1
-  }else{
-    let issues = file_manager::read_issues();
-    let updated = selection::update_issue(args[0].as_str(),
-                                          issues,
-                                          |issue| modify_tag(issue, args[1].as_str(), enabledAfter));
-    match file_manager::write_issues(updated.as_slice()) { 
-      Ok(_) => 0,
-      Err(_) => 2
+pub fn tag_cmd(args:&ParsedArgs, cmdName:&str, enabledAfter:bool) -> isize {
+  let bulkFlags = bulk::flags(args);
+  let ids = args.value("issue-id");
+  //with --where the issue IDs are optional
+  let tag = match args.value("tag") {
+    Some(tag) if ids.is_some() || bulkFlags.query.is_some() => tag,
+    _ => {
+      println!("{} usage: evict {} <issue-id>[,<issue-id>...] <tag>", cmdName, cmdName);
+      println!("       evict {} --where <query> [--yes] <tag>", cmdName);
+      return 1;
+    }
+  };
+  let author = commands::get_author();
+  if bulk::is_bulk(&bulkFlags, ids) {
+    let action = format!("{} {} on", if enabledAfter {"Add tag"} else {"Remove tag"}, tag);
+    return bulk::update_issues(&bulkFlags, ids, action.as_str(),
+                               |issue| modify_tag(issue, tag, &author, enabledAfter));
+  }
+  match commands::update_issue(ids.unwrap_or(""), |issue| modify_tag(issue, tag, &author, enabledAfter)) {
+    Ok(_) => 0,
+    Err(e) => {
+      println!("{}", e);
+      2
     }
   }
 }
 
-fn modify_tag(mut issue:Issue, tag:&str, enabledAfter:bool) -> Issue {
-  let author = commands::get_author();
+fn modify_tag(mut issue:Issue, tag:&str, author:&Author, enabledAfter:bool) -> Issue {
   //Clone early here so we don't have a mut borrow at the same
   //time as the immut borrow that happens here
   let lastTag = {
@@ -54,7 +65,7 @@ fn modify_tag(mut issue:Issue, tag:&str, enabledAfter:bool) -> Issue {
     optTag.map(|tag| tag.clone())
   };
   if lastTag.is_none() || lastTag.unwrap().enabled != enabledAfter {
-    issue.add_tag(IssueTag::new(tag.to_string(), author, enabledAfter));
+    issue.add_tag(IssueTag::new(tag.to_string(), author.clone(), enabledAfter));
   }
   issue
 }
@@ -0,0 +1,68 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use evict::file_manager;
+use evict::journal;
+use evict::serdetime;
+use commands::ParsedArgs;
+
+use std::path::Path;
+
+pub fn undo(args:&ParsedArgs) -> isize {
+  let evictDir = Path::new(file_manager::EVICT_DIRECTORY);
+  if args.has("--list") {
+    return list_operations(evictDir);
+  }
+  let count = match args.value("count") {
+    Some(text) => match usize::from_str_radix(text, 10) {
+      Ok(count) if count > 0 => count,
+      _ => {
+        println!("{} is not a number of operations to undo", text);
+        return 1;
+      }
+    },
+    None => 1
+  };
+  match journal::undo(evictDir, count) {
+    Ok(undone) => {
+      for operation in undone.iter() {
+        println!("Undid `evict {}` ({} file{})", operation.command, operation.files.len(),
+                 if operation.files.len() == 1 {""} else {"s"});
+      }
+      0
+    }
+    Err(msg) => {
+      println!("{}", msg);
+      2
+    }
+  }
+}
+
+fn list_operations(evictDir:&Path) -> isize {
+  let operations = journal::read_journal(evictDir);
+  if operations.len() == 0 {
+    println!("There is nothing to undo");
+  }
+  let now = serdetime::stored_now();
+  for (index, operation) in operations.iter().rev().enumerate() {
+    println!("{}: evict {} ({}, {} file{})", index + 1, operation.command,
+             serdetime::relative_time(&operation.time.0, &now), operation.files.len(),
+             if operation.files.len() == 1 {""} else {"s"});
+  }
+  0
+}
@@ -23,20 +23,59 @@ use serde_json::Error as JsonDeserializationError;
 use serde_json::Result as SerdeResult;
 use serde::{Serialize, Deserialize};
 use std::io::Read;
+use std::collections::BTreeMap;
 
 use std::fs::File;
+use std::path::Path;
 
 static CONFIG_FILE:&'static str = ".evict/config";
+static CONFIG_NAME:&'static str = "config";
+
+///The definition of a custom issue field.  `field_type` is one of
+///the names from custom_fields::FieldType, and `values` lists the
+///allowed values for enum fields.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FieldDefinition{
+  #[serde(rename="type")]
+  pub field_type:String,
+  #[serde(default)]
+  pub values:Vec<String>,
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Config{
   pub author:Option<String>,
+  ///Maps a status name to the statuses an issue may be moved to
+  ///from it.  Statuses without an entry may move to any status.
+  #[serde(default)]
+  pub transitions:BTreeMap<String, Vec<String>>,
+  ///Priority levels, from lowest to highest.
+  #[serde(default)]
+  pub priorities:Vec<String>,
+  ///Custom issue fields, by name.
+  #[serde(default)]
+  pub fields:BTreeMap<String, FieldDefinition>,
+  ///Command aliases, such as `mine` for `list --assignee me --open`.
+  #[serde(default)]
+  pub aliases:BTreeMap<String, String>,
+  ///The template `evict create` uses when none is given.
+  #[serde(default)]
+  pub default_template:Option<String>,
 }
 
 impl Config{
   pub fn load() -> Config {
-    if file_util::file_exists(CONFIG_FILE) {
-      match Config::read_repo_config() {
+    Config::load_from(Path::new(CONFIG_FILE))
+  }
+
+  ///Like load, for the config of the evict directory `evictDir`.
+  pub fn load_in(evictDir:&Path) -> Config {
+    Config::load_from(evictDir.join(CONFIG_NAME).as_path())
+  }
+
+  fn load_from(path:&Path) -> Config {
+    if path.is_file() {
+      match Config::read_repo_config(path) {
         Ok(conf) => conf,
         Err(_) => Config::default()
       }
@@ -46,18 +85,29 @@ impl Config{
   }
   
   pub fn default() -> Config {
-    Config{author:None}
+    Config{author:None, transitions:BTreeMap::new(), priorities:vec!(), fields:BTreeMap::new(),
+           aliases:BTreeMap::new(), default_template:None}
   }
   
-  fn read_repo_config() -> Result<Config, JsonDeserializationError> {
-    let file = try!(File::open(CONFIG_FILE));
+  fn read_repo_config(path:&Path) -> Result<Config, JsonDeserializationError> {
+    let file = try!(File::open(path));
     let mut deser = JsonDeserializer::new(file.bytes());
     Config::deserialize(&mut deser)
   }
   
   pub fn save(&self) -> SerdeResult<()> {
-    let file = try!(File::create(CONFIG_FILE));
-    let mut writer = JsonSerializer::pretty(file);
-    self.serialize(&mut writer)
+    self.save_to(Path::new(CONFIG_FILE))
+  }
+
+  ///Like save, for the config of the evict directory `evictDir`.
+  pub fn save_in(&self, evictDir:&Path) -> SerdeResult<()> {
+    self.save_to(evictDir.join(CONFIG_NAME).as_path())
+  }
+
+  fn save_to(&self, path:&Path) -> SerdeResult<()> {
+    let mut data:Vec<u8> = vec!();
+    try!(self.serialize(&mut JsonSerializer::pretty(&mut data)));
+    try!(file_util::write_atomically(path, data.as_slice()));
+    Ok(())
   }
 }
@@ -0,0 +1,143 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use config;
+use config::FieldDefinition;
+use author::Author;
+use serdetime;
+
+use std::cmp::Ordering;
+
+///The types a custom field can have.  Values are always stored
+///as strings, in the normalized form returned by CustomField::validate.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FieldType{
+  Text,
+  Enum(Vec<String>),
+  Integer,
+  Date,
+  User
+}
+
+#[derive(Clone, Debug)]
+pub struct CustomField{
+  pub name:String,
+  pub field_type:FieldType
+}
+
+impl FieldType{
+  fn from_definition(def:&FieldDefinition) -> Option<FieldType> {
+    match def.field_type.as_str() {
+      "string" => Some(FieldType::Text),
+      "enum" => Some(FieldType::Enum(def.values.clone())),
+      "integer" => Some(FieldType::Integer),
+      "date" => Some(FieldType::Date),
+      "user" => Some(FieldType::User),
+      _ => None
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match *self {
+      FieldType::Text => "string",
+      FieldType::Enum(_) => "enum",
+      FieldType::Integer => "integer",
+      FieldType::Date => "date",
+      FieldType::User => "user"
+    }
+  }
+}
+
+impl CustomField{
+  ///Checks a value against the field's type, returning the value
+  ///in the form it should be stored in or a description of why
+  ///it isn't valid.
+  pub fn validate(&self, value:&str) -> Result<String, String> {
+    let trimmed = value.trim();
+    match self.field_type {
+      FieldType::Text => Ok(value.to_string()),
+      FieldType::Enum(ref values) => {
+        if values.iter().any(|x| x.as_str() == trimmed) {
+          Ok(trimmed.to_string())
+        }else{
+          Err(format!("{} must be one of: {}", self.name, values.join(", ")))
+        }
+      }
+      FieldType::Integer => trimmed.parse::<i64>()
+                                   .map(|x| x.to_string())
+                                   .map_err(|_| format!("{} must be an integer", self.name)),
+      FieldType::Date => serdetime::parse_date(trimmed)
+                          .map(|x| x.strftime(serdetime::DATE_FORMAT).unwrap().to_string())
+                          .ok_or(format!("{} must be a date in the form YYYY-MM-DD", self.name)),
+      FieldType::User => {
+        let author = Author::parse(trimmed);
+        if author.name.len() == 0 {
+          Err(format!("{} must be a user, as Name or Name <email>", self.name))
+        }else{
+          Ok(author.to_string())
+        }
+      }
+    }
+  }
+
+  ///Compares two stored values of this field.  Integers compare
+  ///numerically, enum values by their position in the definition
+  ///and everything else as text, which is also chronological for
+  ///dates in YYYY-MM-DD form.
+  pub fn compare(&self, a:&str, b:&str) -> Ordering {
+    match self.field_type {
+      FieldType::Integer => a.parse::<i64>().ok().cmp(&b.parse::<i64>().ok()),
+      FieldType::Enum(ref values) => {
+        let pos = |v:&str| values.iter().position(|x| x.as_str() == v);
+        pos(a).cmp(&pos(b))
+      }
+      _ => a.cmp(b)
+    }
+  }
+}
+
+///Reads the custom fields defined in the repo config.  Fields
+///with an unknown type are ignored.
+pub fn read_field_definitions() -> Vec<CustomField> {
+  config::Config::load().fields.iter().filter_map(|(name, def)| {
+    FieldType::from_definition(def).map(|t| CustomField{name:name.clone(), field_type:t})
+  }).collect()
+}
+
+pub fn find_field(name:&str) -> Option<CustomField> {
+  read_field_definitions().into_iter().find(|f| f.name.as_str() == name)
+}
+
+#[test]
+pub fn validate_field_values(){
+  let severity = CustomField{name:"severity".to_string(),
+                             field_type:FieldType::Enum(vec!("minor".to_string(),
+                                                             "major".to_string()))};
+  assert_eq!(severity.validate("major"), Ok("major".to_string()));
+  assert!(severity.validate("huge").is_err());
+  assert_eq!(severity.compare("minor", "major"), Ordering::Less);
+
+  let count = CustomField{name:"count".to_string(), field_type:FieldType::Integer};
+  assert_eq!(count.validate(" 12 "), Ok("12".to_string()));
+  assert!(count.validate("twelve").is_err());
+  assert_eq!(count.compare("9", "10"), Ordering::Less);
+
+  let due = CustomField{name:"due".to_string(), field_type:FieldType::Date};
+  assert_eq!(due.validate("2014-05-21"), Ok("2014-05-21".to_string()));
+  assert!(due.validate("May 21").is_err());
+}
@@ -17,7 +17,6 @@
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
 use issue::{Issue, IssueTimelineEvent};
-use issue::IssueTimelineEvent::{TimelineComment, TimelineTag};
 use time;
 use std::mem::swap;
 
@@ -34,10 +33,7 @@ impl TimeSorted{
   fn creation(&self) -> time::Tm {
     match self {
       &TimeSortedIssue(ref issue) => issue.creation_time(),
-      &TimeSortedEvent(ref evt) => match evt {
-        &TimelineComment(ref comment) => comment.creation_time.0,
-        &TimelineTag(ref tag) => tag.time.0
-      }
+      &TimeSortedEvent(ref evt) => *evt.time()
     }
   }
   
@@ -87,14 +83,19 @@ pub fn sort_by_time(issues:Vec<Issue>) -> Vec<Issue>{
   let mut sorted:Vec<Issue> = wrapped.into_iter().map(|x| x.unwrap_to_issue()).collect();
   
   for x in sorted.iter_mut() {
-    let mut events:Vec<IssueTimelineEvent> = vec!();
-    swap(&mut events, &mut x.events);
-    
-    let mut wrappedComments:Vec<TimeSorted> = events.into_iter().map(|x| TimeSortedEvent(x)).collect();
-    wrappedComments.sort_by(ts_ordering);
-    events = wrappedComments.into_iter().map(|x| x.unwrap_to_event()).collect();
-    swap(&mut events, &mut x.events);
+    sort_events(&mut x.events);
   }
   sorted
 }
 
+///Sorts events oldest first.  Accessors such as Issue::all_tags and
+///Issue::priority rely on this order.
+pub fn sort_events(events:&mut Vec<IssueTimelineEvent>) {
+  let mut unsorted:Vec<IssueTimelineEvent> = vec!();
+  swap(&mut unsorted, events);
+
+  let mut wrappedComments:Vec<TimeSorted> = unsorted.into_iter().map(|x| TimeSortedEvent(x)).collect();
+  wrappedComments.sort_by(ts_ordering);
+  *events = wrappedComments.into_iter().map(|x| x.unwrap_to_event()).collect();
+}
+
@@ -18,13 +18,24 @@
  */
 use issue::{Issue, IssueTimelineEvent, IssueJsonParseError};
 use file_util;
+use file_util::StagedFile;
+use links;
+use date_sort;
+use hooks;
+use journal;
+use journal::FileChange;
+use repo_lock::RepoLock;
 use std::io::Error as IoError;
+use std::io::ErrorKind;
 use serde_json::Result as SerdeResult;
 use serde_json;
 use std::fs;
-use std::fs::File;
+use serde::Serialize;
 
 use std::path::{Path, PathBuf};
+use std::env;
+use std::thread;
+use libc;
 
 
 #[cfg(not(test))]
@@ -44,7 +55,7 @@ error_type! {
   }
 }
 
-static ISSUE_DIRECTORY:&'static str = "issue-dirs";
+pub static ISSUE_DIRECTORY:&'static str = "issue-dirs";
 
 static BODY_FILENAME:&'static str = "body";
 
@@ -58,6 +69,197 @@ pub fn single_issue_filename(issue:&Issue) -> String {
   format!("{}/{}/{}", EVICT_DIRECTORY, ISSUE_DIRECTORY, issue.id())
 }
 
+///An issue as it was read and as it is to be saved.  Saving an
+///IssueChange only writes the parts of the issue which differ, so
+///the cost of a command doesn't grow with the size of the repo and
+///git diffs only contain the actual change.
+pub struct IssueChange{
+  pub before:Option<Issue>,
+  pub after:Issue
+}
+
+impl IssueChange{
+  pub fn new(before:Issue, after:Issue) -> IssueChange {
+    IssueChange{before:Some(before), after:after}
+  }
+
+  ///A change which creates a new issue.
+  pub fn created(issue:Issue) -> IssueChange {
+    IssueChange{before:None, after:issue}
+  }
+
+  ///Makes a change from an issue, applying `update` to a copy of it.
+  pub fn apply<UF:FnOnce(Issue) -> Issue>(issue:Issue, update:UF) -> IssueChange {
+    let updated = update(issue.clone());
+    IssueChange::new(issue, updated)
+  }
+
+  ///Writes the body if it changed and each event which is new or
+  ///changed.  Events are never deleted, so nothing is removed.
+  ///Hooks run and warnings are returned as for save_changes.
+  pub fn save(&self) -> SerdeResult<Vec<String>> {
+    self.save_in(issue_directory_path().as_path())
+  }
+
+  ///Like save, for the issue directory `issueDir`.
+  pub fn save_in(&self, issueDir:&Path) -> SerdeResult<Vec<String>> {
+    save_changes_in(&[self], issueDir)
+  }
+
+  ///Runs the pre hooks for this change, stopping at the first to fail.
+  fn run_pre_hooks(&self, evictDir:&Path) -> SerdeResult<()> {
+    for (action, event) in hooks::actions(self.before.as_ref(), &self.after).into_iter() {
+      try!(hooks::run_pre_hook(evictDir, action, &self.after, event));
+    }
+    Ok(())
+  }
+
+  ///Checks that nobody else changed the issue's body since `before`
+  ///was read, if this change replaces it.  Events are new files, so
+  ///changes which only add events can't overwrite each other.
+  fn check_current(&self, issueDir:&Path) -> SerdeResult<()> {
+    let before = match self.before {
+      Some(ref before) if before.no_comment_json() != self.after.no_comment_json() => before,
+      _ => return Ok(())
+    };
+    let path = issueDir.join(before.id()).join(BODY_FILENAME);
+    let onDisk = file_util::read_string_from_path(path.as_path()).ok()
+                   .and_then(|data| serde_json::from_str::<serde_json::Value>(data.as_str()).ok());
+    if onDisk.map_or(false, |current| current != before.no_comment_json()) {
+      let msg = format!("Issue {} was changed by another evict process; run the command again",
+                        before.id());
+      return Err(IoError::new(ErrorKind::Other, msg).into());
+    }
+    Ok(())
+  }
+
+  fn run_post_hooks(&self, evictDir:&Path, warnings:&mut Vec<String>) {
+    for (action, event) in hooks::actions(self.before.as_ref(), &self.after).into_iter() {
+      warnings.extend(hooks::run_post_hook(evictDir, action, &self.after, event));
+    }
+  }
+
+  ///Stages the files for this change without running hooks, adding
+  ///them to `staged`.  Nothing is visible until they are committed.
+  fn stage_in(&self, issueDir:&Path, staged:&mut Vec<(StagedFile, FileChange)>) -> SerdeResult<()> {
+    let issue = &self.after;
+    if !issueDir.is_dir() {
+      try!(fs::create_dir(issueDir));
+    }
+    let dir = issueDir.join(issue.id());
+    file_util::create_directory_path(dir.as_path());
+    let bodyChanged = self.before.as_ref().map_or(true, |before| {
+      before.no_comment_json() != issue.no_comment_json()
+    });
+    if bodyChanged {
+      try!(stage_json(issueDir, issue.id(), BODY_FILENAME, &issue.no_comment_json(), staged));
+    }
+    for event in issue.events.iter() {
+      let unchanged = self.before.as_ref().map_or(false, |before| {
+        before.events.iter().any(|old| old == event)
+      });
+      if !unchanged {
+        try!(stage_json(issueDir, issue.id(), event.id(), event, staged));
+      }
+    }
+    Ok(())
+  }
+}
+
+pub fn save_changes(changes:&[IssueChange]) -> SerdeResult<Vec<String>> {
+  let refs:Vec<&IssueChange> = changes.iter().collect();
+  save_changes_in(refs.as_slice(), issue_directory_path().as_path())
+}
+
+///Saves changes to the issues in `issueDir`.  Every change is the
+///same to hooks, whichever command made it: the pre hooks for all of
+///the changes run first, and if one fails nothing is written.  The
+///files written are journaled as one operation, for evict undo.
+///
+///Every file is staged before any is renamed into place, so a
+///failed write leaves the issues untouched.  If a rename fails part
+///way, the files already replaced are put back as they were.
+///
+///The repository lock is only held while saving, so commands can
+///read issues and wait on the user without locking out others.  If
+///another process changed an issue in the meantime, nothing is saved.
+///Pre hooks run while the lock is held, so they can't run evict
+///commands which change issues.  It is released before the post
+///hooks run, so those can, unless the calling command holds the
+///lock itself.
+///
+///Once the changes are written, a journal which can't be recorded or
+///a post hook which fails doesn't undo them.  Those are returned as
+///warnings for the caller to show.
+pub fn save_changes_in(changes:&[&IssueChange], issueDir:&Path) -> SerdeResult<Vec<String>> {
+  let evictDir = issueDir.parent().unwrap_or(Path::new(""));
+  let lock = try!(RepoLock::acquire_in(evictDir).map_err(|msg| IoError::new(ErrorKind::Other, msg)));
+  for change in changes.iter() {
+    try!(change.check_current(issueDir));
+  }
+  for change in changes.iter() {
+    try!(change.run_pre_hooks(evictDir));
+  }
+  let mut staged = vec!();
+  for change in changes.iter() {
+    if let Err(e) = change.stage_in(issueDir, &mut staged) {
+      for &(ref file, _) in staged.iter() {
+        file.discard();
+      }
+      return Err(e);
+    }
+  }
+  try!(commit_staged(staged.as_slice()));
+  let written = staged.into_iter().map(|(_, change)| change).collect();
+  let mut warnings = vec!();
+  //the changes are made either way, so a journal error only means they can't be undone
+  if let Err(e) = journal::record(evictDir, written) {
+    warnings.push(format!("Couldn't record the change for evict undo: {}", e));
+  }
+  drop(lock);
+  for change in changes.iter() {
+    change.run_post_hooks(evictDir, &mut warnings);
+  }
+  Ok(warnings)
+}
+
+///Renames every staged file into place.  If one fails, the rest are
+///discarded and those already renamed get their old contents back.
+fn commit_staged(staged:&[(StagedFile, FileChange)]) -> SerdeResult<()> {
+  for (index, &(ref file, _)) in staged.iter().enumerate() {
+    if let Err(e) = file.commit() {
+      for &(ref rest, _) in staged[index + 1..].iter() {
+        rest.discard();
+      }
+      for &(ref done, ref change) in staged[..index].iter().rev() {
+        let _ = match change.before {
+          Some(ref before) => file_util::write_atomically(done.path.as_path(), before.as_bytes()).map(|_| ()),
+          None => fs::remove_file(done.path.as_path())
+        };
+      }
+      return Err(e.into());
+    }
+  }
+  Ok(())
+}
+
+///Stages `value` as the file `filename` of issue `issueId`, along
+///with the change for the journal, if the file's content changed.
+fn stage_json<T:Serialize>(issueDir:&Path, issueId:&str, filename:&str, value:&T,
+                           staged:&mut Vec<(StagedFile, FileChange)>) -> SerdeResult<()> {
+  let path = issueDir.join(issueId).join(filename);
+  let data = try!(serde_json::to_string_pretty(value));
+  let before = file_util::read_string_from_path(path.as_path()).ok();
+  if let Some(file) = try!(file_util::stage_file(path.as_path(), data.as_bytes())) {
+    let relative = Path::new(ISSUE_DIRECTORY).join(issueId).join(filename);
+    staged.push((file, FileChange{path:relative.to_string_lossy().into_owned(), before:before, after:data}));
+  }
+  Ok(())
+}
+
+///Writes every part of each issue.  Only files whose content
+///changed are replaced, but every file is still compared, so
+///commands changing a few issues should use save_changes.
 pub fn write_issues(issues:&[Issue]) -> SerdeResult<()> {
   if !issue_directory_path().is_dir() {
     try!(fs::create_dir(issue_directory_path()));
@@ -88,8 +290,7 @@ fn write_single_issue(issue:&Issue) -> SerdeResult<()> {
 
 fn write_issue_body(issue:&Issue) -> SerdeResult<()> {
   let filename = issue_body_filename(issue);
-  let mut file_out = try!(File::create(filename));
-  serde_json::to_writer_pretty(&mut file_out, &issue.no_comment_json())
+  write_json(filename.as_str(), &issue.no_comment_json())
 }
 
 fn issue_body_filename(issue:&Issue) -> String {
@@ -98,58 +299,181 @@ fn issue_body_filename(issue:&Issue) -> String {
 
 fn write_issue_event(issueId:&str, event:&IssueTimelineEvent) -> SerdeResult<()>{
   let filename = issue_event_filename(issueId, event);
-  let mut output_file = try!(File::create(filename.as_str()));
-  serde_json::to_writer_pretty(&mut output_file, event)
+  write_json(filename.as_str(), event)
+}
+
+///Writes a value as pretty-printed JSON, replacing the file
+///atomically and only if its content changed.
+pub fn write_json<T:Serialize>(filename:&str, value:&T) -> SerdeResult<()> {
+  let data = try!(serde_json::to_string_pretty(value));
+  try!(file_util::write_atomically(Path::new(filename), data.as_bytes()));
+  Ok(())
 }
 
 fn issue_event_filename(issueId:&str, event:&IssueTimelineEvent) -> String {
   format!("{}/{}/{}/{}", EVICT_DIRECTORY, ISSUE_DIRECTORY, issueId, event.id())
 }
 
+///Which parts of each issue to read.  Events are stored in a file
+///each, so reading only the body is much faster when tags, comments
+///and other events aren't needed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReadParts{
+  Everything,
+  BodyOnly
+}
+
+///Below this many issues, starting threads costs more than it saves.
+static PARALLEL_THRESHOLD:usize = 64;
+static DEFAULT_THREADS:usize = 4;
+
 pub fn read_issues() -> Vec<Issue> {
-  read_issues_from_folders()
+  let mut issues = read_issues_in(issue_directory_path().as_path(), ReadParts::Everything);
+  links::compute_reverse_links(issues.as_mut_slice());
+  issues
 }
 
-fn read_issues_from_folders() -> Vec<Issue> {
-  /*! Reads all issues from the folders located in the
-   *  folder returned by full_issue_directory.
-   *  If a folder/file in the issue directory does not parse
-   *  as an issue, it is ignored.
-   */
-  let dirPath = issue_directory_path();
-  let issueDirResult = fs::read_dir(&dirPath);
+///Reads the issues without their events.
+pub fn read_issue_bodies() -> Vec<Issue> {
+  read_issues_in(issue_directory_path().as_path(), ReadParts::BodyOnly)
+}
+
+///Reads all issues from the issue directories in `issueDir`.
+///If a folder/file in the issue directory does not parse as an
+///issue, it is ignored.
+pub fn read_issues_in(issueDir:&Path, parts:ReadParts) -> Vec<Issue> {
+  read_issue_dirs(issue_dirs_in(issueDir), parts)
+}
 
+///Reads the issues in the given issue directories, in parallel if
+///there are enough of them.  Directories which can't be read as an
+///issue are left out, so the result may be shorter than `dirs`.
+pub fn read_issue_dirs(dirs:Vec<PathBuf>, parts:ReadParts) -> Vec<Issue> {
+  read_issue_dirs_with_threads(dirs, parts, thread_count())
+}
+
+pub fn read_issue_dirs_with_threads(dirs:Vec<PathBuf>, parts:ReadParts, threads:usize)
+  -> Vec<Issue> {
+  if dirs.len() < PARALLEL_THRESHOLD || threads < 2 {
+    return dirs.into_iter().filter_map(|dir| read_issue_parts(dir, parts).ok()).collect();
+  }
+  let chunkSize = (dirs.len() + threads - 1) / threads;
+  let mut chunks:Vec<Vec<PathBuf>> = vec!();
+  let mut remaining = dirs.into_iter();
+  loop {
+    let chunk:Vec<PathBuf> = remaining.by_ref().take(chunkSize).collect();
+    if chunk.len() == 0 {
+      break;
+    }
+    chunks.push(chunk);
+  }
+  let handles:Vec<thread::JoinHandle<Vec<Issue>>> = chunks.into_iter().map(|chunk| {
+    thread::spawn(move || {
+      chunk.into_iter().filter_map(|dir| read_issue_parts(dir, parts).ok()).collect()
+    })
+  }).collect();
+  let mut issues:Vec<Issue> = vec!();
+  for handle in handles.into_iter() {
+    //a thread only fails if reading panicked, and then those issues are lost either way
+    issues.extend(handle.join().unwrap_or(vec!()));
+  }
+  issues
+}
+
+///The number of threads to read issues with: EVICT_THREADS if it
+///is set, and otherwise the number of processors.
+fn thread_count() -> usize {
+  match env::var("EVICT_THREADS").ok().and_then(|n| usize::from_str_radix(n.trim(), 10).ok()) {
+    Some(count) => count,
+    None => processor_count().unwrap_or(DEFAULT_THREADS)
+  }
+}
+
+#[cfg(unix)]
+fn processor_count() -> Option<usize> {
+  let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+  if count > 0 {Some(count as usize)} else {None}
+}
+
+#[cfg(not(unix))]
+fn processor_count() -> Option<usize> {
+  None
+}
+
+pub fn issue_dirs_in(issueDir:&Path) -> Vec<PathBuf> {
   //There aren't any issue directories to deal with
   //so just return an empty list
-  if issueDirResult.is_err() {
-    return vec![];
+  match fs::read_dir(issueDir) {
+    Ok(entries) => entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect(),
+    Err(_) => vec!()
   }
+}
 
-  let issueDirs = issueDirResult.ok().unwrap();
-  
-  issueDirs.into_iter().filter_map (
-    |path| read_issue_from_dir(path.unwrap().path()).ok()
-  ).collect()
+///Reads issues one at a time, so callers which only need some of
+///them can stop early without reading the rest.
+pub struct IssueReader{
+  dirs:Option<fs::ReadDir>,
+  parts:ReadParts
 }
 
+impl Iterator for IssueReader{
+  type Item = Issue;
+
+  fn next(&mut self) -> Option<Issue> {
+    let parts = self.parts;
+    match self.dirs {
+      Some(ref mut dirs) => dirs.filter_map(|entry| entry.ok())
+                                .filter_map(|entry| read_issue_parts(entry.path(), parts).ok())
+                                .next(),
+      None => None
+    }
+  }
+}
 
-fn read_issue_from_dir(basePath:PathBuf) -> Result<Issue, DataReadError> {
-  let files = fs::read_dir(&basePath);
+///Lazily reads the issues in the repository.  Reverse links aren't
+///computed, since they need every issue.
+pub fn issue_reader(parts:ReadParts) -> IssueReader {
+  issue_reader_in(issue_directory_path().as_path(), parts)
+}
+
+pub fn issue_reader_in(issueDir:&Path, parts:ReadParts) -> IssueReader {
+  IssueReader{dirs:fs::read_dir(issueDir).ok(), parts:parts}
+}
+
+///Reads the issue with the given full ID, if it exists.
+pub fn read_issue(id:&str) -> Option<Issue> {
+  read_issue_from_dir(issue_directory_path().join(id)).ok()
+}
+
+pub fn read_issue_from_dir(basePath:PathBuf) -> Result<Issue, DataReadError> {
+  read_issue_parts(basePath, ReadParts::Everything)
+}
+
+fn read_issue_parts(basePath:PathBuf, parts:ReadParts) -> Result<Issue, DataReadError> {
   let bodyPath = Path::new(BODY_FILENAME);
   let issueBodyPath = basePath.join(bodyPath);
-  let noBodyFiles:Vec<PathBuf> = files.ok().unwrap()
-                                 .into_iter()
-                                 .map(|dir_entry| dir_entry.unwrap().path())
-                                 .filter(|path| *path != issueBodyPath)
+  if parts == ReadParts::BodyOnly {
+    return read_issue_body(issueBodyPath);
+  }
+  let files = try!(fs::read_dir(&basePath));
+  //names starting with '.' are temporary files left by an interrupted write
+  let noBodyFiles:Vec<PathBuf> = files.into_iter()
+                                 .filter_map(|dir_entry| dir_entry.ok().map(|e| e.path()))
+                                 .filter(|path| *path != issueBodyPath && !is_hidden(path))
                                  .collect();
   let bodyIssue = read_issue_body(issueBodyPath);
   bodyIssue.map (|mut bIssue| {
-    let events = read_issue_events(noBodyFiles.as_slice());
-    bIssue.events = events;
+    //read_dir lists the event files in no particular order
+    bIssue.events = read_issue_events(noBodyFiles.as_slice());
+    date_sort::sort_events(&mut bIssue.events);
     bIssue
   })
 }
 
+fn is_hidden(path:&Path) -> bool {
+  path.file_name().map_or(false, |name| name.to_string_lossy().starts_with("."))
+}
+
 fn read_issue_body(bodyPath:PathBuf) -> Result<Issue, DataReadError> {
   /*! Reads an issue from a file, except for the comments, which are stored
    *  separately from other data.
@@ -170,10 +494,11 @@ fn read_comment(commentFile:&PathBuf) -> Result<IssueTimelineEvent, serde_json::
 #[test]
 pub fn write_read_issue_file(){
   use std::error::Error;
+  use author::Author;
 
   file_util::create_directory_path(&Path::new(EVICT_DIRECTORY));
   file_util::create_directory_path(&issue_directory_path());
-  let issues = vec!(Issue::new("A".to_string(), "B".to_string(), "C".to_string()));
+  let issues = vec!(Issue::new("A".to_string(), "B".to_string(), Author::parse("C")));
   let write_res = write_issues(issues.as_slice());
   assert!(
     write_res.is_ok(),
@@ -185,3 +510,57 @@ pub fn write_read_issue_file(){
   assert!(issues == read);
   let _ = fs::remove_dir_all(&Path::new(EVICT_DIRECTORY));
 }
+
+///Writes an issue with a priority event for each of `priorities`,
+///set a minute apart in that order.  The event files are named so
+///they list in the opposite order to their times.
+#[cfg(test)]
+fn write_prioritized_issue(issueDir:&Path, priorities:&[&str]) -> Issue {
+  use author::Author;
+  use issue::{IssuePriority, IssueTimelineEvent};
+  use serdetime::SerdeTime;
+  use time;
+
+  let issue = Issue::new("A".to_string(), "B".to_string(), Author::parse("C"));
+  let dir = issueDir.join(issue.id());
+  fs::create_dir_all(&dir).unwrap();
+  write_json(dir.join(BODY_FILENAME).to_string_lossy().as_ref(), &issue.no_comment_json()).unwrap();
+  for (index, priority) in priorities.iter().enumerate() {
+    let time = time::at_utc(time::Timespec::new(1400000000 + 60 * index as i64, 0));
+    let event = IssueTimelineEvent::TimelinePriority(
+      IssuePriority{time:SerdeTime(time), priority:priority.to_string(), author:Author::parse("C"),
+                    change_id:format!("{}", priorities.len() - index)});
+    write_json(dir.join(event.id()).to_string_lossy().as_ref(), &event).unwrap();
+  }
+  issue
+}
+
+#[test]
+pub fn read_issue_sorts_events_by_time(){
+  let issueDir = env::temp_dir().join(format!("evict-read-test-{}", ::time::precise_time_ns()));
+  let issue = write_prioritized_issue(issueDir.as_path(), &["low", "high", "medium", "urgent"]);
+  let read = read_issue_from_dir(issueDir.join(issue.id())).unwrap();
+  assert_eq!(read.priority(), Some("urgent"));
+  let _ = fs::remove_dir_all(&issueDir);
+}
+
+#[test]
+pub fn every_reader_sorts_events_by_time(){
+  let issueDir = env::temp_dir().join(format!("evict-readers-test-{}", ::time::precise_time_ns()));
+  let issues:Vec<Issue> = (0 .. PARALLEL_THRESHOLD).map(|_| {
+    write_prioritized_issue(issueDir.as_path(), &["high", "low"])
+  }).collect();
+
+  let read = read_issue_from_dir(issueDir.join(issues[0].id())).unwrap();
+  assert_eq!(read.priority(), Some("low"));
+
+  let lazy:Vec<Issue> = issue_reader_in(issueDir.as_path(), ReadParts::Everything).collect();
+  assert_eq!(lazy.len(), issues.len());
+  assert!(lazy.iter().all(|issue| issue.priority() == Some("low")));
+
+  //enough issues, and threads, to read in parallel
+  let parallel = read_issue_dirs_with_threads(issue_dirs_in(issueDir.as_path()), ReadParts::Everything, 4);
+  assert_eq!(parallel.len(), issues.len());
+  assert!(parallel.iter().all(|issue| issue.priority() == Some("low")));
+  let _ = fs::remove_dir_all(&issueDir);
+}
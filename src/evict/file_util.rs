@@ -16,7 +16,8 @@
  *   You should have received a copy of the GNU General Public License
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::env;
 use std::io::Result as IoResult;
 use std::io::Error as IoError;
 use std::io::ErrorKind;
@@ -28,10 +29,88 @@ pub fn write_string_to_file(content:&str, filename:&str, overwrite:bool) -> IoRe
   if !overwrite && file_exists(filename) {
     Err(IoError::new(ErrorKind::AlreadyExists, "File already exists and should not be overwritten"))
   }else{
-    //successful by default, then set to false
-    //if an error is encountered
-    fs::File::create(&Path::new(filename)).and_then(
-                    |mut f| f.write(content.to_string().into_bytes().as_slice()).map(|_| ()))
+    write_atomically(&Path::new(filename), content.as_bytes()).map(|_| ())
+  }
+}
+
+///Replaces the contents of `path` so that a crash leaves either the
+///old or the new contents, never a mix.  The data is written to a
+///temporary file next to `path`, synced to disk, then renamed over
+///it.  If the file already has exactly this content it is left
+///alone, and false is returned.
+pub fn write_atomically(path:&Path, content:&[u8]) -> IoResult<bool> {
+  match try!(stage_file(path, content)) {
+    Some(staged) => {
+      try!(staged.commit());
+      Ok(true)
+    }
+    None => Ok(false)
+  }
+}
+
+///New contents for a file, written and synced next to it but not yet
+///renamed over it.  Staging every file first lets a set of writes be
+///abandoned before any of them is visible.
+pub struct StagedFile{
+  pub path:PathBuf,
+  temp:PathBuf
+}
+
+impl StagedFile{
+  ///Renames the new contents over the file.  The staged copy is
+  ///removed if that fails.
+  pub fn commit(&self) -> IoResult<()> {
+    match fs::rename(&self.temp, &self.path) {
+      Ok(_) => {
+        sync_parent(self.path.as_path());
+        Ok(())
+      }
+      Err(e) => {
+        self.discard();
+        Err(e)
+      }
+    }
+  }
+
+  ///Removes the staged copy, leaving the file as it was.
+  pub fn discard(&self) {
+    let _ = fs::remove_file(&self.temp);
+  }
+}
+
+///Stages `content` to replace `path`, see StagedFile.  Returns None
+///if the file already has exactly this content.
+pub fn stage_file(path:&Path, content:&[u8]) -> IoResult<Option<StagedFile>> {
+  if let Ok(mut existing) = fs::File::open(path) {
+    let mut current = vec!();
+    if existing.read_to_end(&mut current).is_ok() && current.as_slice() == content {
+      return Ok(None);
+    }
+  }
+  let name = path.file_name().map_or("".to_string(), |name| name.to_string_lossy().into_owned());
+  let tempPath = path.with_file_name(format!(".{}.tmp-{}", name, ::issue::generate_id()));
+  let written = fs::File::create(&tempPath).and_then(|mut f| {
+    try!(f.write_all(content));
+    f.sync_all()
+  });
+  match written {
+    Ok(_) => Ok(Some(StagedFile{path:path.to_path_buf(), temp:tempPath})),
+    Err(e) => {
+      let _ = fs::remove_file(&tempPath);
+      Err(e)
+    }
+  }
+}
+
+///Syncs the directory containing `path`, so a rename into it is
+///durable.  Not all platforms allow this, so failures are ignored.
+fn sync_parent(path:&Path) {
+  let parent = match path.parent() {
+    Some(parent) if parent.as_os_str().len() > 0 => parent,
+    _ => Path::new(".")
+  };
+  if let Ok(dir) = fs::File::open(parent) {
+    let _ = dir.sync_all();
   }
 }
 pub fn read_string_from_file(filename:&str) -> IoResult<String> {
@@ -43,6 +122,47 @@ pub fn read_string_from_path(path:&Path) -> IoResult<String> {
   fs::File::open(path).and_then(|mut f| f.read_to_string(&mut string)).map(|_| string)
 }
 
+///Creates a new file in the system temp directory which only the
+///current user can read, containing `content`.  The name starts
+///with `prefix` and is unique, so concurrent runs don't clash.
+pub fn create_private_temp_file(prefix:&str, content:&str) -> IoResult<PathBuf> {
+  loop {
+    let path = env::temp_dir().join(format!("evict-{}-{}", prefix, ::issue::generate_id()));
+    match private_options().open(&path) {
+      Ok(mut f) => return f.write_all(content.as_bytes()).map(|_| path),
+      Err(ref e) if e.kind() == ErrorKind::AlreadyExists => continue,
+      Err(e) => return Err(e)
+    }
+  }
+}
+
+#[cfg(unix)]
+fn private_options() -> fs::OpenOptions {
+  use std::os::unix::fs::OpenOptionsExt;
+  let mut options = fs::OpenOptions::new();
+  options.write(true).create_new(true).mode(0o600);
+  options
+}
+
+#[cfg(not(unix))]
+fn private_options() -> fs::OpenOptions {
+  let mut options = fs::OpenOptions::new();
+  options.write(true).create_new(true);
+  options
+}
+
+///Whether `path` is a file which can be run as a program.
+#[cfg(unix)]
+pub fn is_executable(path:&Path) -> bool {
+  use std::os::unix::fs::PermissionsExt;
+  fs::metadata(path).map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+pub fn is_executable(path:&Path) -> bool {
+  fs::metadata(path).map(|m| m.is_file()).unwrap_or(false)
+}
+
 pub fn file_exists(name:&str) -> bool {
   Path::new(name).exists()
 }
@@ -108,3 +228,31 @@ pub fn write_read_str(){
   assert_eq!(file_string.unwrap(), testString);
   assert!(delete_file(testname));
 }
+
+#[test]
+pub fn atomic_write_skips_unchanged(){
+  let testname = "file_util_testAWSU";
+  let path = Path::new(testname);
+
+  assert_eq!(write_atomically(&path, b"first").ok(), Some(true));
+  assert_eq!(write_atomically(&path, b"first").ok(), Some(false));
+  assert_eq!(write_atomically(&path, b"second").ok(), Some(true));
+  assert_eq!(read_string_from_file(testname).ok(), Some("second".to_string()));
+  assert!(delete_file(testname));
+}
+
+#[test]
+pub fn staged_file_is_invisible_until_committed(){
+  let testname = "file_util_testSFIU";
+  let path = Path::new(testname);
+
+  assert_eq!(write_atomically(&path, b"old").ok(), Some(true));
+  let discarded = stage_file(&path, b"dropped").unwrap().unwrap();
+  discarded.discard();
+  let kept = stage_file(&path, b"new").unwrap().unwrap();
+  assert_eq!(read_string_from_file(testname).ok(), Some("old".to_string()));
+  assert!(kept.commit().is_ok());
+  assert_eq!(read_string_from_file(testname).ok(), Some("new".to_string()));
+  assert!(stage_file(&path, b"new").unwrap().is_none());
+  assert!(delete_file(testname));
+}
@@ -0,0 +1,146 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use file_util;
+use issue::{Issue, IssueTimelineEvent};
+use issue::IssueTimelineEvent::{TimelineComment, TimelineTag, TimelineStatus};
+use serde_json;
+use serde_json::value::Value as JsonValue;
+
+use std::collections::BTreeMap;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub static HOOK_DIRECTORY:&'static str = "hooks";
+
+///Something done to an issue which hooks can run for.  Each has a
+///`pre-` hook, run before the change is written, which can stop it
+///by exiting non-zero, and a `post-` hook run afterwards.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HookAction{
+  Create,
+  StatusChange,
+  Comment,
+  Tag
+}
+
+impl HookAction{
+  pub fn as_str(&self) -> &'static str {
+    match *self {
+      HookAction::Create => "create",
+      HookAction::StatusChange => "status-change",
+      HookAction::Comment => "comment",
+      HookAction::Tag => "tag"
+    }
+  }
+}
+
+///The actions in a change from `before` to `after`, each with the
+///event it added.  Creating an issue has no event.
+pub fn actions<'x>(before:Option<&Issue>, after:&'x Issue)
+    -> Vec<(HookAction, Option<&'x IssueTimelineEvent>)> {
+  let before = match before {
+    Some(before) => before,
+    None => return vec!((HookAction::Create, None))
+  };
+  after.events.iter().filter(|event| !before.events.contains(event)).filter_map(|event| {
+    match *event {
+      TimelineComment(_) => Some((HookAction::Comment, Some(event))),
+      TimelineTag(_) => Some((HookAction::Tag, Some(event))),
+      TimelineStatus(_) => Some((HookAction::StatusChange, Some(event))),
+      _ => None
+    }
+  }).collect()
+}
+
+///Runs the `pre-` hook for an action, giving an error if it fails.
+pub fn run_pre_hook(evictDir:&Path, action:HookAction, issue:&Issue,
+                    event:Option<&IssueTimelineEvent>) -> Result<(), IoError> {
+  let name = format!("pre-{}", action.as_str());
+  match run_hook(evictDir, name.as_str(), issue, event) {
+    Some(Ok(true)) | None => Ok(()),
+    Some(Ok(false)) => Err(IoError::new(ErrorKind::Other,
+                                        format!("The {} hook stopped the change to issue {}",
+                                                name, issue.id()))),
+    Some(Err(e)) => Err(IoError::new(ErrorKind::Other,
+                                     format!("Couldn't run the {} hook: {}", name, e)))
+  }
+}
+
+///Runs the `post-` hook for an action.  The change has already been
+///written, so a failing hook is only returned as a warning.
+pub fn run_post_hook(evictDir:&Path, action:HookAction, issue:&Issue,
+                     event:Option<&IssueTimelineEvent>) -> Option<String> {
+  let name = format!("post-{}", action.as_str());
+  match run_hook(evictDir, name.as_str(), issue, event) {
+    Some(Ok(false)) => Some(format!("The {} hook failed for issue {}", name, issue.id())),
+    Some(Err(e)) => Some(format!("Couldn't run the {} hook: {}", name, e)),
+    None | Some(Ok(true)) => None
+  }
+}
+
+///Runs `.evict/hooks/<name>` from the repository root, if it exists,
+///with the issue and event as JSON on stdin.  Returns whether it
+///exited successfully.
+fn run_hook(evictDir:&Path, name:&str, issue:&Issue, event:Option<&IssueTimelineEvent>)
+    -> Option<Result<bool, IoError>> {
+  let path = evictDir.join(HOOK_DIRECTORY).join(name);
+  if !file_util::is_executable(path.as_path()) {
+    return None;
+  }
+  let mut command = Command::new(&path);
+  command.env("EVICT_HOOK", name).stdin(Stdio::piped());
+  if let Some(root) = evictDir.parent() {
+    if root != Path::new("") {
+      command.current_dir(root);
+    }
+  }
+  let mut child = match command.spawn() {
+    Ok(child) => child,
+    Err(e) => return Some(Err(e))
+  };
+  if let Some(mut stdin) = child.stdin.take() {
+    //a hook which doesn't read its input shouldn't fail the write
+    let _ = stdin.write_all(hook_input(name, issue, event).as_bytes());
+  }
+  Some(child.wait().map(|status| status.success()))
+}
+
+fn hook_input(name:&str, issue:&Issue, event:Option<&IssueTimelineEvent>) -> String {
+  let mut map = BTreeMap::new();
+  map.insert("hook".to_string(), JsonValue::String(name.to_string()));
+  map.insert("issue".to_string(), issue.no_comment_json());
+  map.insert("event".to_string(), event.map_or(JsonValue::Null, |e| serde_json::value::to_value(e)));
+  serde_json::to_string(&JsonValue::Object(map)).unwrap_or("{}".to_string())
+}
+
+#[test]
+pub fn actions_are_new_events(){
+  use author::Author;
+  use issue::{IssueComment, IssueTag};
+  let before = Issue::new("A".to_string(), "".to_string(), Author::parse("X"));
+  assert_eq!(actions(None, &before), vec!((HookAction::Create, None)));
+  let mut after = before.clone();
+  after.events.push(TimelineComment(IssueComment::new(Author::parse("X"), "hi".to_string())));
+  after.events.push(TimelineTag(IssueTag::new("bug".to_string(), Author::parse("X"), true)));
+  let kinds:Vec<HookAction> = actions(Some(&before), &after).into_iter().map(|(a, _)| a).collect();
+  assert_eq!(kinds, vec!(HookAction::Comment, HookAction::Tag));
+}
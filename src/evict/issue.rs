@@ -23,8 +23,11 @@ use serde_json::value::from_value as from_json_value;
 
 use time;
 use vcs_status;
+use author::Author;
 use status_storage::DEFAULT_STATUS_NAME;
-use self::IssueTimelineEvent::{TimelineComment, TimelineTag};
+use self::IssueTimelineEvent::{TimelineComment, TimelineTag, TimelinePriority,
+                               TimelineMilestone, TimelineLink, TimelineField,
+                               TimelineAssign, TimelineCommentEdit, TimelineStatus};
 
 use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
@@ -88,13 +91,26 @@ impl From<JsonError> for IssueJsonParseError {
 }
 
 
+///Where an event was copied from, when an issue is
+///marked as a duplicate of another.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EventOrigin{
+  pub issue_id: String,
+  pub event_id: String
+}
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct IssueComment{
   pub creation_time: SerdeTime,
-  pub author:String,
+  pub author:Author,
   pub body_text:String,
   pub branch:String,
-  pub id:String
+  pub id:String,
+  #[serde(default)]
+  pub copied_from:Option<EventOrigin>,
+  ///The ID of the comment this one replies to, if any.
+  #[serde(default)]
+  pub reply_to:Option<String>
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -102,14 +118,101 @@ pub struct IssueTag{
   pub time: SerdeTime,
   pub tag_name: String,
   pub enabled: bool,
-  pub author: String,
+  pub author: Author,
+  pub change_id: String,
+  #[serde(default)]
+  pub copied_from:Option<EventOrigin>
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IssuePriority{
+  pub time: SerdeTime,
+  pub priority: String,
+  pub author: Author,
+  pub change_id: String
+}
+
+///Assigns an issue to a milestone, or removes it from
+///its milestone if `milestone` is None.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IssueMilestone{
+  pub time: SerdeTime,
+  pub milestone: Option<String>,
+  pub author: Author,
+  pub change_id: String
+}
+
+///Adds (or removes, if not enabled) a link of type `link_type`
+///from this issue to the issue with ID `target`.  Link types
+///are the names from links::LinkType.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IssueLink{
+  pub time: SerdeTime,
+  pub link_type: String,
+  pub target: String,
+  pub enabled: bool,
+  pub author: Author,
+  pub change_id: String
+}
+
+///Sets the custom field `field` to `value`, or clears
+///it if `value` is None.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IssueField{
+  pub time: SerdeTime,
+  pub field: String,
+  pub value: Option<String>,
+  pub author: Author,
+  pub change_id: String
+}
+
+///Assigns an issue to someone, or unassigns it if
+///`assignee` is None.  `create --assignee` records one of these,
+///and `evict assign`/`unassign` add later ones, so an assignment
+///made at creation can be changed and merges like any other event.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IssueAssignment{
+  pub time: SerdeTime,
+  pub assignee: Option<Author>,
+  pub author: Author,
+  pub change_id: String
+}
+
+///Replaces the text of the comment with ID `comment_id`, or
+///deletes the comment if `body_text` is None.  The original
+///comment is kept, so the history survives merges.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IssueCommentEdit{
+  pub time: SerdeTime,
+  pub comment_id: String,
+  pub body_text: Option<String>,
+  pub author: Author,
+  pub change_id: String
+}
+
+///Records a change of the issue's status from `from` to `to`.
+///The current status is kept in Issue.status; these events
+///are only the history.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IssueStatusChange{
+  pub time: SerdeTime,
+  pub from: String,
+  pub to: String,
+  pub author: Author,
   pub change_id: String
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum IssueTimelineEvent{
   TimelineComment(IssueComment),
-  TimelineTag(IssueTag)
+  TimelineTag(IssueTag),
+  TimelinePriority(IssuePriority),
+  TimelineMilestone(IssueMilestone),
+  TimelineLink(IssueLink),
+  TimelineField(IssueField),
+  TimelineAssign(IssueAssignment),
+  TimelineCommentEdit(IssueCommentEdit),
+  TimelineStatus(IssueStatusChange)
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -122,7 +225,7 @@ pub struct IssueStatus{
 pub struct IssueBase {
   pub title:String,
   pub creation_time: SerdeTime, 
-  pub author: String,
+  pub author: Author,
   pub id: String,
   pub branch: String,
   pub body_text:String,
@@ -133,6 +236,10 @@ pub struct Issue{
   pub events:Vec<IssueTimelineEvent>,
   pub status:IssueStatus,
   pub base: IssueBase,
+  ///Links from other issues to this one, as (reverse link name, source ID)
+  ///pairs.  These are never written, and are filled in by
+  ///links::compute_reverse_links when issues are read.
+  pub reverse_links: Vec<(String, String)>,
 }
 
 
@@ -158,8 +265,8 @@ impl Issue{
     self.base.creation_time.0
   }
 
-  pub fn author(&self) -> &str {
-    self.base.author.as_str()
+  pub fn author(&self) -> &Author {
+    &self.base.author
   }
 
   pub fn id(&self) -> &str {
@@ -185,6 +292,166 @@ impl Issue{
     self.events.push(TimelineTag(tag))
   }
 
+  pub fn set_priority(&mut self, priority:IssuePriority) {
+    self.events.push(TimelinePriority(priority))
+  }
+
+  ///Returns the most recently set priority of this issue, if
+  ///it has ever been given one.  Like all_tags, this assumes the
+  ///events are sorted by date.
+  pub fn priority(&self) -> Option<&str> {
+    for evt in self.events.iter().rev() {
+      match evt {
+        &TimelinePriority(ref priority) => return Some(priority.priority.as_str()),
+        _ => {}
+      }
+    }
+    None
+  }
+
+  pub fn add_link(&mut self, link:IssueLink) {
+    self.events.push(TimelineLink(link))
+  }
+
+  ///Returns all links from this issue which are currently enabled, as
+  ///(link type, target ID) pairs.  Like all_tags, this assumes the events
+  ///are sorted by date.
+  pub fn links(&self) -> Vec<(String, String)> {
+    let mut seen:Vec<(&str, &str)> = vec!();
+    let mut link_list:Vec<(String, String)> = vec!();
+    for evt in self.events.iter().rev() {
+      match evt {
+        &TimelineLink(ref link) => {
+          let key = (link.link_type.as_str(), link.target.as_str());
+          if !seen.contains(&key) {
+            seen.push(key);
+            if link.enabled {
+              link_list.push((link.link_type.clone(), link.target.clone()));
+            }
+          }
+        }
+        _ => {}
+      }
+    }
+    link_list
+  }
+
+  pub fn has_link(&self, link_type:&str, target:&str) -> bool {
+    self.links().iter().any(|&(ref t, ref id)| t.as_str() == link_type && id.as_str() == target)
+  }
+
+  pub fn assign(&mut self, assignment:IssueAssignment) {
+    self.events.push(TimelineAssign(assignment))
+  }
+
+  ///Returns who the issue is currently assigned to.  Assumes
+  ///the events are sorted by date.
+  pub fn assignee(&self) -> Option<&Author> {
+    for evt in self.events.iter().rev() {
+      match evt {
+        &TimelineAssign(ref assignment) => return assignment.assignee.as_ref(),
+        _ => {}
+      }
+    }
+    None
+  }
+
+  ///Sets the status of the issue, recording the change in the
+  ///timeline if the status is different.
+  pub fn change_status(&mut self, status:IssueStatus, author:Author) {
+    if status.name != self.status.name {
+      let change = IssueStatusChange::new(self.status.name.clone(), status.name.clone(), author);
+      self.events.push(TimelineStatus(change));
+    }
+    self.status = status;
+  }
+
+  ///Finds the comments on this issue whose ID ends with `idPart`.
+  pub fn find_comments<'x>(&'x self, idPart:&str) -> Vec<&'x IssueComment> {
+    self.events.iter().filter_map(|evt| match evt {
+      &TimelineComment(ref comment) if comment.id.ends_with(idPart) => Some(comment),
+      _ => None
+    }).collect()
+  }
+
+  pub fn edit_comment(&mut self, edit:IssueCommentEdit) {
+    self.events.push(TimelineCommentEdit(edit))
+  }
+
+  ///Returns the current text of a comment on this issue, taking
+  ///edits into account, or None if the comment has been deleted.
+  ///Assumes the events are sorted by date.
+  pub fn comment_text<'x>(&'x self, comment:&'x IssueComment) -> Option<&'x str> {
+    for evt in self.events.iter().rev() {
+      match evt {
+        &TimelineCommentEdit(ref edit) if edit.comment_id == comment.id =>
+          return edit.body_text.as_ref().map(|text| text.as_str()),
+        _ => {}
+      }
+    }
+    Some(comment.body_text.as_str())
+  }
+
+  ///Checks whether a comment on this issue has been edited or deleted.
+  pub fn comment_edited(&self, comment:&IssueComment) -> bool {
+    self.events.iter().any(|evt| match evt {
+      &TimelineCommentEdit(ref edit) => edit.comment_id == comment.id,
+      _ => false
+    })
+  }
+
+  pub fn set_field(&mut self, field:IssueField) {
+    self.events.push(TimelineField(field))
+  }
+
+  ///Returns the current value of a custom field.  Assumes
+  ///the events are sorted by date.
+  pub fn field(&self, name:&str) -> Option<&str> {
+    for evt in self.events.iter().rev() {
+      match evt {
+        &TimelineField(ref field) if field.field.as_str() == name =>
+          return field.value.as_ref().map(|x| x.as_str()),
+        _ => {}
+      }
+    }
+    None
+  }
+
+  ///Returns all custom fields which currently have a value,
+  ///as (field, value) pairs.
+  pub fn fields(&self) -> Vec<(String, String)> {
+    let mut seen:Vec<&str> = vec!();
+    let mut field_list:Vec<(String, String)> = vec!();
+    for evt in self.events.iter().rev() {
+      match evt {
+        &TimelineField(ref field) if !seen.contains(&field.field.as_str()) => {
+          seen.push(field.field.as_str());
+          for value in field.value.iter() {
+            field_list.push((field.field.clone(), value.clone()));
+          }
+        }
+        _ => {}
+      }
+    }
+    field_list
+  }
+
+  pub fn set_milestone(&mut self, milestone:IssueMilestone) {
+    self.events.push(TimelineMilestone(milestone))
+  }
+
+  ///Returns the name of the milestone this issue is currently
+  ///assigned to.  Assumes the events are sorted by date.
+  pub fn milestone(&self) -> Option<&str> {
+    for evt in self.events.iter().rev() {
+      match evt {
+        &TimelineMilestone(ref assigned) => return assigned.milestone.as_ref().map(|x| x.as_str()),
+        _ => {}
+      }
+    }
+    None
+  }
+
   pub fn most_recent_tag_for_name<'x>(&'x self, name:&str) -> Option<&'x IssueTag> {
     let mut recent:Option<&'x IssueTag> = None;
     for evt in self.events.iter(){
@@ -254,10 +521,10 @@ impl Issue{
     match json {
       JsonValue::Object(map) => Issue::read_from_map(map),
       _ => Err(IssueJsonParseError::UnexpectedJsonValue)
-    }.map(|x| ::date_sort::sort_by_time(vec!(x)).pop().unwrap())
-    // [ugly] Fix date sorting individual issue events
-    // This will fix the line above.  Probably just means splitting out
-    // part of date_sort::sort_by_time
+    }.map(|mut x| {
+      ::date_sort::sort_events(&mut x.events);
+      x
+    })
   }
 
   fn read_from_map(mut map:JsonObjectMap) -> Result<Issue, IssueJsonParseError>{
@@ -272,13 +539,13 @@ impl Issue{
 
     match base_opt {
       Some(r) => 
-        r.map(|base| Issue{ events: vec!(), status: state, base: base })
+        r.map(|base| Issue{ events: vec!(), status: state, base: base, reverse_links: vec!() })
          .map_err(Into::into),
       None => Err(IssueJsonParseError::KeyNotFound("base".to_string()))
     }
   }
 
-  pub fn new(title:String, body:String, author:String) -> Issue{
+  pub fn new(title:String, body:String, author:Author) -> Issue{
     let branch = vcs_status::current_branch().unwrap_or("<unknown>".to_string());
     Issue{
       base: IssueBase{
@@ -290,24 +557,78 @@ impl Issue{
         body_text: body
       },
       events:vec!(),
-      status:IssueStatus::default()
+      status:IssueStatus::default(),
+      reverse_links:vec!()
     }
   }
 
 }
 
 impl IssueTag{
-  pub fn new(name:String, author:String, enabled:bool) -> IssueTag{
+  pub fn new(name:String, author:Author, enabled:bool) -> IssueTag{
     IssueTag{time:SerdeTime(time::now()), author:author, enabled:enabled,
-             tag_name:name, change_id:generate_id()}
+             tag_name:name, change_id:generate_id(), copied_from:None}
+  }
+}
+
+impl IssuePriority{
+  pub fn new(priority:String, author:Author) -> IssuePriority{
+    IssuePriority{time:SerdeTime(time::now()), author:author,
+                  priority:priority, change_id:generate_id()}
+  }
+}
+
+impl IssueMilestone{
+  pub fn new(milestone:Option<String>, author:Author) -> IssueMilestone{
+    IssueMilestone{time:SerdeTime(time::now()), author:author,
+                   milestone:milestone, change_id:generate_id()}
+  }
+}
+
+impl IssueLink{
+  pub fn new(link_type:String, target:String, author:Author, enabled:bool) -> IssueLink{
+    IssueLink{time:SerdeTime(time::now()), author:author, enabled:enabled,
+              link_type:link_type, target:target, change_id:generate_id()}
+  }
+}
+
+impl IssueField{
+  pub fn new(field:String, value:Option<String>, author:Author) -> IssueField{
+    IssueField{time:SerdeTime(time::now()), author:author,
+               field:field, value:value, change_id:generate_id()}
+  }
+}
+
+impl IssueAssignment{
+  pub fn new(assignee:Option<Author>, author:Author) -> IssueAssignment{
+    IssueAssignment{time:SerdeTime(time::now()), author:author,
+                    assignee:assignee, change_id:generate_id()}
   }
 }
 
 impl IssueComment{
-  pub fn new(author:String, body:String) -> IssueComment{
+  pub fn new(author:Author, body:String) -> IssueComment{
     let branch = vcs_status::current_branch().unwrap_or("<unknown>".to_string());
     IssueComment{author:author, body_text:body, creation_time:SerdeTime(time::now()),
-                  branch: branch, id:generate_id()}
+                  branch: branch, id:generate_id(), copied_from:None, reply_to:None}
+  }
+
+  pub fn reply(author:Author, body:String, reply_to:String) -> IssueComment{
+    IssueComment{reply_to:Some(reply_to), .. IssueComment::new(author, body)}
+  }
+}
+
+impl IssueStatusChange{
+  pub fn new(from:String, to:String, author:Author) -> IssueStatusChange{
+    IssueStatusChange{time:SerdeTime(time::now()), author:author, from:from, to:to,
+                      change_id:generate_id()}
+  }
+}
+
+impl IssueCommentEdit{
+  pub fn new(comment_id:String, body:Option<String>, author:Author) -> IssueCommentEdit{
+    IssueCommentEdit{time:SerdeTime(time::now()), author:author, comment_id:comment_id,
+                     body_text:body, change_id:generate_id()}
   }
 }
 
@@ -315,21 +636,80 @@ impl IssueTimelineEvent{
   pub fn event_type(&self) -> String {
     match self {
       &TimelineComment(_) => "comment",
-      &TimelineTag(_) => "tag"
+      &TimelineTag(_) => "tag",
+      &TimelinePriority(_) => "priority",
+      &TimelineMilestone(_) => "milestone",
+      &TimelineLink(_) => "link",
+      &TimelineField(_) => "field",
+      &TimelineAssign(_) => "assign",
+      &TimelineCommentEdit(_) => "comment-edit",
+      &TimelineStatus(_) => "status"
     }.to_string()
   }
 
+  pub fn author(&self) -> &Author {
+    match self {
+      &TimelineComment(ref comment) => &comment.author,
+      &TimelineTag(ref tag) => &tag.author,
+      &TimelinePriority(ref priority) => &priority.author,
+      &TimelineMilestone(ref assigned) => &assigned.author,
+      &TimelineLink(ref link) => &link.author,
+      &TimelineField(ref field) => &field.author,
+      &TimelineAssign(ref assignment) => &assignment.author,
+      &TimelineCommentEdit(ref edit) => &edit.author,
+      &TimelineStatus(ref change) => &change.author
+    }
+  }
+
   pub fn time<'x>(&'x self) -> &'x time::Tm {
     match self {
       &TimelineComment(ref comment) => &comment.creation_time.0,
-      &TimelineTag(ref tag) => &tag.time.0
+      &TimelineTag(ref tag) => &tag.time.0,
+      &TimelinePriority(ref priority) => &priority.time.0,
+      &TimelineMilestone(ref assigned) => &assigned.time.0,
+      &TimelineLink(ref link) => &link.time.0,
+      &TimelineField(ref field) => &field.time.0,
+      &TimelineAssign(ref assignment) => &assignment.time.0,
+      &TimelineCommentEdit(ref edit) => &edit.time.0,
+      &TimelineStatus(ref change) => &change.time.0
+    }
+  }
+
+  ///Returns a copy of this event for another issue, with a new ID
+  ///and a record of where it came from.  Only comments and tags
+  ///are copied; other events return None.
+  pub fn copy_from_issue(&self, issue_id:&str) -> Option<IssueTimelineEvent> {
+    let origin = EventOrigin{issue_id:issue_id.to_string(), event_id:self.id().to_string()};
+    match self {
+      &TimelineComment(ref comment) =>
+        Some(TimelineComment(IssueComment{id:generate_id(), copied_from:Some(origin),
+                                          .. comment.clone()})),
+      &TimelineTag(ref tag) =>
+        Some(TimelineTag(IssueTag{change_id:generate_id(), copied_from:Some(origin),
+                                  .. tag.clone()})),
+      _ => None
+    }
+  }
+
+  pub fn copied_from(&self) -> Option<&EventOrigin> {
+    match self {
+      &TimelineComment(ref comment) => comment.copied_from.as_ref(),
+      &TimelineTag(ref tag) => tag.copied_from.as_ref(),
+      _ => None
     }
   }
 
   pub fn id<'x>(&'x self) -> &'x str {
     match self {
       &TimelineComment(ref comment) => comment.id.as_str(),
-      &TimelineTag(ref tag) => tag.change_id.as_str()
+      &TimelineTag(ref tag) => tag.change_id.as_str(),
+      &TimelinePriority(ref priority) => priority.change_id.as_str(),
+      &TimelineMilestone(ref assigned) => assigned.change_id.as_str(),
+      &TimelineLink(ref link) => link.change_id.as_str(),
+      &TimelineField(ref field) => field.change_id.as_str(),
+      &TimelineAssign(ref assignment) => assignment.change_id.as_str(),
+      &TimelineCommentEdit(ref edit) => edit.change_id.as_str(),
+      &TimelineStatus(ref change) => change.change_id.as_str()
     }
   }
 }
@@ -348,10 +728,10 @@ pub fn generate_id() -> String {
 
 #[test]
 pub fn issue_equality(){
-  let i1 = Issue::new("A".to_string(), "B".to_string(), "C".to_string());
-  let mut i2 = Issue::new("X".to_string(), "Y".to_string(), "Z".to_string());
+  let i1 = Issue::new("A".to_string(), "B".to_string(), Author::parse("C"));
+  let mut i2 = Issue::new("X".to_string(), "Y".to_string(), Author::parse("Z"));
   i2.base.id = i1.id().to_string();  //hackery because ids are generated by Issue::new
-  let i3 = Issue::new("D".to_string(), "E".to_string(), "F".to_string());
+  let i3 = Issue::new("D".to_string(), "E".to_string(), Author::parse("F"));
   //identify by ids
   assert!(i1 == i2);
   assert!(i2 != i3);
@@ -361,11 +741,11 @@ pub fn issue_equality(){
 pub fn write_and_read_issue_json(){
   let title = "Foo".to_string();
   let body = "Body".to_string();
-  let author = "Author".to_string();
+  let author = Author::parse("Author <author@example.com>");
 
   let issue = Issue::new(title.to_string(), 
                          body.to_string(),
-                         author.to_string());
+                         author.clone());
 
   let json = issue.no_comment_json();
   println!("{:?}", json);
@@ -378,7 +758,7 @@ pub fn write_and_read_issue_json(){
 
   assert!(read_issue == issue);
   assert!(read_issue.title() == title);
-  assert!(read_issue.author() == author);
+  assert!(*read_issue.author() == author);
   assert!(read_issue.id() == issue.id());
   assert!(time::strftime(TIME_FORMAT, &read_issue.creation_time()) == 
           time::strftime(TIME_FORMAT, &issue.creation_time()));
@@ -0,0 +1,254 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use file_manager;
+use file_manager::ReadParts;
+use file_util;
+use journal;
+use issue::Issue;
+use links::LinkType;
+use author::Author;
+use serdetime::SerdeTime;
+use serde_json;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+static CACHE_FILE:&'static str = "cache";
+static GITIGNORE_FILE:&'static str = ".gitignore";
+///Bumped whenever IssueSummary changes, so old caches are rebuilt.
+static CACHE_VERSION:u32 = 2;
+///Short IDs are never shorter than this, even if fewer digits
+///would be unique.
+static MIN_SHORT_ID:usize = 4;
+
+///The parts of an issue needed to list it and resolve its ID,
+///kept in `.evict/cache` so they don't have to be read from
+///every issue's files each time.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IssueSummary{
+  pub id:String,
+  pub title:String,
+  pub status:String,
+  pub tags:Vec<String>,
+  pub author:Author,
+  pub created:SerdeTime,
+  pub modified:SerdeTime,
+  pub links:Vec<(String, String)>,
+  ///The shortest suffix of the ID which no other issue shares.
+  ///Not stored, as it depends on the other issues.
+  #[serde(skip_serializing, default)]
+  pub short_id:String
+}
+
+impl IssueSummary{
+  pub fn new(issue:&Issue) -> IssueSummary {
+    let modified = issue.events.iter().map(|evt| *evt.time())
+                               .chain(Some(issue.status.last_change_time.0).into_iter())
+                               .chain(Some(issue.creation_time()).into_iter())
+                               .max_by_key(|time| time.to_timespec())
+                               .unwrap();
+    IssueSummary{id:issue.id().to_string(),
+                 title:issue.title().to_string(),
+                 status:issue.status.name.clone(),
+                 tags:issue.all_tags(),
+                 author:issue.author().clone(),
+                 created:SerdeTime(issue.creation_time()),
+                 modified:SerdeTime(modified),
+                 links:issue.links(),
+                 short_id:"".to_string()}
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry{
+  stamp:String,
+  summary:IssueSummary
+}
+
+#[derive(Serialize, Deserialize)]
+struct IssueCache{
+  version:u32,
+  entries:BTreeMap<String, CacheEntry>
+}
+
+fn cache_filename(evictDir:&Path) -> PathBuf {
+  evictDir.join(CACHE_FILE)
+}
+
+fn read_cache(evictDir:&Path) -> BTreeMap<String, CacheEntry> {
+  file_util::read_string_from_path(cache_filename(evictDir).as_path()).ok()
+            .and_then(|data| serde_json::from_str::<IssueCache>(data.as_str()).ok())
+            .and_then(|cache| if cache.version == CACHE_VERSION {Some(cache.entries)} else {None})
+            .unwrap_or(BTreeMap::new())
+}
+
+///Returns a summary of every issue, reading only the issues which
+///changed since the cache was last written.  The cache is updated
+///if anything changed.
+pub fn read_summaries() -> Vec<IssueSummary> {
+  read_summaries_in(Path::new(file_manager::EVICT_DIRECTORY))
+}
+
+///Like read_summaries, for the evict directory `evictDir`.
+pub fn read_summaries_in(evictDir:&Path) -> Vec<IssueSummary> {
+  let mut cached = read_cache(evictDir);
+  let mut entries:BTreeMap<String, CacheEntry> = BTreeMap::new();
+  let mut stale:Vec<(PathBuf, String)> = vec!();
+  for path in file_manager::issue_dirs_in(evictDir.join(file_manager::ISSUE_DIRECTORY).as_path()) {
+    let name = match path.file_name() {
+      Some(name) => name.to_string_lossy().into_owned(),
+      None => continue
+    };
+    let stamp = match directory_stamp(&path) {
+      Some(stamp) => stamp,
+      None => continue
+    };
+    match cached.remove(&name) {
+      Some(entry) if entry.stamp == stamp => {
+        entries.insert(name, entry);
+      }
+      _ => stale.push((path, stamp))
+    }
+  }
+  //anything left in the old cache was deleted
+  let changed = stale.len() > 0 || cached.len() > 0;
+  let (stalePaths, stamps):(Vec<PathBuf>, Vec<String>) = stale.into_iter().unzip();
+  let stamps:BTreeMap<String, String> = stalePaths.iter()
+    .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+    .zip(stamps.into_iter())
+    .collect();
+  for issue in file_manager::read_issue_dirs(stalePaths, ReadParts::Everything).into_iter() {
+    if let Some(stamp) = stamps.get(issue.id()) {
+      entries.insert(issue.id().to_string(),
+                     CacheEntry{stamp:stamp.clone(), summary:IssueSummary::new(&issue)});
+    }
+  }
+  if changed {
+    let cache = IssueCache{version:CACHE_VERSION, entries:entries};
+    write_cache(evictDir, &cache);
+    entries = cache.entries;
+  }
+  let mut summaries:Vec<IssueSummary> = entries.into_iter().map(|(_, entry)| entry.summary).collect();
+  assign_short_ids(summaries.as_mut_slice());
+  summaries
+}
+
+///Identifies the state of an issue directory.  Every write renames
+///a file into the directory, which updates its modification time,
+///and the body's size and time are included in case the filesystem
+///only records times to the second.
+fn directory_stamp(path:&Path) -> Option<String> {
+  let dirTime = try_stamp_time(path);
+  let count = fs::read_dir(path).ok().map_or(0, |files| files.count());
+  let body = path.join("body");
+  let bodyLen = fs::metadata(&body).ok().map_or(0, |meta| meta.len());
+  dirTime.map(|time| format!("{}:{}:{}:{}", time, count, try_stamp_time(&body).unwrap_or(0), bodyLen))
+}
+
+fn try_stamp_time(path:&Path) -> Option<u64> {
+  fs::metadata(path).ok()
+    .and_then(|meta| meta.modified().ok())
+    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    .map(|since| since.as_secs() * 1000000000 + since.subsec_nanos() as u64)
+}
+
+///Saves the cache.  Failing to save it only makes the next
+///command slower, so errors are ignored.  Repositories created before
+///the cache existed get it ignored in git here, since `evict init`
+///can't be run on them again.
+fn write_cache(evictDir:&Path, cache:&IssueCache) {
+  ignore_in_git(evictDir);
+  let _ = file_manager::write_json(cache_filename(evictDir).to_string_lossy().as_ref(), cache);
+}
+
+///The reverse links to issue `id`, as Issue::reverse_links would
+///hold them, worked out from the links in `summaries`.
+pub fn reverse_links(id:&str, summaries:&[IssueSummary]) -> Vec<(String, String)> {
+  summaries.iter().flat_map(|summary| {
+    summary.links.iter()
+      .filter(|&&(_, ref target)| target.as_str() == id)
+      .filter_map(|&(ref link_type, _)| LinkType::from_str(link_type.as_str()))
+      .map(|lt| (lt.reverse_name().to_string(), summary.id.clone()))
+      .collect::<Vec<(String, String)>>()
+  }).collect()
+}
+
+///Makes sure git ignores the files in `.evict` which are local to
+///this checkout: the cache, the lock and the undo journal.  The
+///`.gitignore` is only written if one of them is missing from it.
+pub fn ignore_in_git(evictDir:&Path) {
+  let path = evictDir.join(GITIGNORE_FILE);
+  let filename = path.to_string_lossy();
+  let current = file_util::read_string_from_file(filename.as_ref()).unwrap_or("".to_string());
+  let mut updated = current.clone();
+  for ignored in [CACHE_FILE, "lock", journal::JOURNAL_DIRECTORY].iter() {
+    if !current.lines().any(|line| line.trim() == *ignored) {
+      if updated.len() > 0 && !updated.ends_with("\n") {
+        updated.push('\n');
+      }
+      updated.push_str(ignored);
+      updated.push('\n');
+    }
+  }
+  if updated != current {
+    let _ = file_util::write_string_to_file(updated.as_str(), filename.as_ref(), true);
+  }
+}
+
+///Sets each summary's short_id to the shortest suffix of its ID,
+///of at least MIN_SHORT_ID characters, which ends no other ID.
+pub fn assign_short_ids(summaries:&mut [IssueSummary]) {
+  let mut sorted:Vec<String> = summaries.iter()
+                                        .map(|s| s.id.chars().rev().collect::<String>())
+                                        .collect();
+  sorted.sort();
+  for summary in summaries.iter_mut() {
+    let reversed:String = summary.id.chars().rev().collect();
+    let index = sorted.binary_search(&reversed).unwrap_or(0);
+    //only the neighbours in sorted order can share a long suffix
+    let shared = [index.checked_sub(1), Some(index + 1)].iter()
+      .filter_map(|neighbour| neighbour.and_then(|n| sorted.get(n)))
+      .map(|other| common_prefix(other.as_str(), reversed.as_str()))
+      .max()
+      .unwrap_or(0);
+    let length = ::std::cmp::min(::std::cmp::max(shared + 1, MIN_SHORT_ID), summary.id.len());
+    summary.short_id = summary.id[summary.id.len() - length ..].to_string();
+  }
+}
+
+fn common_prefix(a:&str, b:&str) -> usize {
+  a.chars().zip(b.chars()).take_while(|&(x, y)| x == y).count()
+}
+
+#[test]
+pub fn short_ids_are_unique_suffixes(){
+  let mut summaries:Vec<IssueSummary> = ["1399720517980750949", "1399720517980760949",
+                                         "1400000000000000123"].iter().map(|id| {
+    let mut summary = IssueSummary::new(&Issue::new("A".to_string(), "B".to_string(),
+                                                    Author::parse("C")));
+    summary.id = id.to_string();
+    summary
+  }).collect();
+  assign_short_ids(summaries.as_mut_slice());
+  assert_eq!(summaries[0].short_id, "50949".to_string());
+  assert_eq!(summaries[1].short_id, "60949".to_string());
+  assert_eq!(summaries[2].short_id, "0123".to_string());
+}
@@ -0,0 +1,212 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use file_manager;
+use file_util;
+use serdetime;
+use serdetime::SerdeTime;
+use serde_json;
+use vcs_status;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+///The directory in the evict directory holding one file per operation,
+///named by a sequence number so they sort oldest first.
+pub static JOURNAL_DIRECTORY:&'static str = "journal";
+///Only this many operations are kept; older ones can't be undone.
+static JOURNAL_LIMIT:usize = 100;
+
+///A file written by an operation, with its path relative to the
+///evict directory.  `before` is None if the file was created.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FileChange{
+  pub path:String,
+  pub before:Option<String>,
+  pub after:String
+}
+
+///The issue files written by one command, so it can be undone.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Operation{
+  pub time:SerdeTime,
+  ///The command line which made the changes.
+  pub command:String,
+  pub files:Vec<FileChange>
+}
+
+fn journal_dir(evictDir:&Path) -> PathBuf {
+  evictDir.join(JOURNAL_DIRECTORY)
+}
+
+///The sequence numbers and paths of the operation files, oldest
+///first.  Partly written files don't have numeric names, so they're
+///skipped.
+fn operation_files(evictDir:&Path) -> Vec<(u64, PathBuf)> {
+  let mut files:Vec<(u64, PathBuf)> = match fs::read_dir(journal_dir(evictDir)) {
+    Ok(entries) => entries.filter_map(|entry| entry.ok())
+                          .filter_map(|entry| {
+                            let number = entry.file_name().to_str()
+                                              .and_then(|name| u64::from_str_radix(name, 10).ok());
+                            number.map(|n| (n, entry.path()))
+                          })
+                          .collect(),
+    Err(_) => vec!()
+  };
+  files.sort();
+  files
+}
+
+fn read_operation(path:&Path) -> Option<Operation> {
+  file_util::read_string_from_path(path).ok()
+            .and_then(|data| serde_json::from_str(data.as_str()).ok())
+}
+
+///Reads the journal, oldest operation first.
+pub fn read_journal(evictDir:&Path) -> Vec<Operation> {
+  operation_files(evictDir).iter().filter_map(|&(_, ref path)| read_operation(path.as_path())).collect()
+}
+
+///Adds an operation made of `files` to the journal, for the command
+///this process was run as.  Only the new operation is written, and
+///the oldest are removed once there are more than JOURNAL_LIMIT.
+pub fn record(evictDir:&Path, files:Vec<FileChange>) -> serde_json::Result<()> {
+  if files.len() == 0 {
+    return Ok(());
+  }
+  let command:Vec<String> = env::args().skip(1).collect();
+  let existing = operation_files(evictDir);
+  let next = existing.last().map_or(1, |&(number, _)| number + 1);
+  let dir = journal_dir(evictDir);
+  try!(fs::create_dir_all(&dir));
+  let operation = Operation{time:SerdeTime(serdetime::stored_now()),
+                            command:command.join(" "),
+                            files:files};
+  try!(file_manager::write_json(dir.join(format!("{:020}", next)).to_string_lossy().as_ref(),
+                                &operation));
+  if existing.len() + 1 > JOURNAL_LIMIT {
+    for &(_, ref path) in existing[.. existing.len() + 1 - JOURNAL_LIMIT].iter() {
+      let _ = fs::remove_file(path);
+    }
+  }
+  Ok(())
+}
+
+///Checks that `operations`, newest first, can be undone: each file
+///must still be as the operation left it (allowing for the newer
+///operations being undone first) and not committed since.
+fn check_undo(evictDir:&Path, operations:&[Operation]) -> Result<(), String> {
+  let mut current:BTreeMap<String, Option<String>> = BTreeMap::new();
+  for operation in operations.iter() {
+    for file in operation.files.iter() {
+      let path = evictDir.join(file.path.as_str());
+      let content = match current.get(&file.path) {
+        Some(content) => content.clone(),
+        None => file_util::read_string_from_path(path.as_path()).ok()
+      };
+      if content.as_ref() != Some(&file.after) {
+        return Err(format!("{} has been changed since `evict {}`, so it can't be undone",
+                           path.display(), operation.command));
+      }
+      if vcs_status::committed_content(path.as_path()).as_ref() == Some(&file.after) {
+        return Err(format!("`evict {}` has been committed, so it can't be undone.  \
+                            Revert the commit instead.", operation.command));
+      }
+      current.insert(file.path.clone(), file.before.clone());
+    }
+  }
+  Ok(())
+}
+
+///Undoes the last `count` operations, newest first, returning them.
+///Nothing is changed if any of them can't be undone.
+pub fn undo(evictDir:&Path, count:usize) -> Result<Vec<Operation>, String> {
+  let mut files = operation_files(evictDir);
+  if count > files.len() {
+    return Err(format!("Only {} operation{} can be undone", files.len(),
+                       if files.len() == 1 {""} else {"s"}));
+  }
+  let start = files.len() - count;
+  let undoneFiles:Vec<PathBuf> = files.drain(start ..).rev().map(|(_, path)| path).collect();
+  let mut undone:Vec<Operation> = vec!();
+  for path in undoneFiles.iter() {
+    match read_operation(path.as_path()) {
+      Some(operation) => undone.push(operation),
+      None => return Err(format!("Couldn't read {}", path.display()))
+    }
+  }
+  try!(check_undo(evictDir, undone.as_slice()));
+  for operation in undone.iter() {
+    for file in operation.files.iter().rev() {
+      let path = evictDir.join(file.path.as_str());
+      let result = match file.before {
+        Some(ref before) => file_util::write_atomically(path.as_path(), before.as_bytes()).map(|_| ()),
+        None => fs::remove_file(&path).map(|_| remove_if_empty(path.parent()))
+      };
+      if let Err(e) = result {
+        return Err(format!("Couldn't restore {}: {}", path.display(), e));
+      }
+    }
+  }
+  for path in undoneFiles.iter() {
+    try!(fs::remove_file(path).map_err(|e| format!("Couldn't remove {}: {}", path.display(), e)));
+  }
+  Ok(undone)
+}
+
+///Removes an issue directory left empty by undoing its creation.
+fn remove_if_empty(dir:Option<&Path>) {
+  if let Some(dir) = dir {
+    let empty = fs::read_dir(dir).map(|mut entries| entries.next().is_none()).unwrap_or(false);
+    if empty {
+      let _ = fs::remove_dir(dir);
+    }
+  }
+}
+
+#[test]
+pub fn undo_restores_files_unless_changed(){
+  let evictDir = env::temp_dir().join(format!("evict-journal-test-{}", ::time::precise_time_ns()));
+  let issueDir = evictDir.join("issue-dirs").join("x");
+  file_util::create_directory_path(issueDir.as_path());
+  let write = |name:&str, content:&str| {
+    file_util::write_atomically(issueDir.join(name).as_path(), content.as_bytes()).unwrap();
+  };
+  write("body", "new");
+  write("event", "created");
+  let change = |name:&str, before:Option<&str>, after:&str| {
+    FileChange{path:format!("issue-dirs/x/{}", name), before:before.map(|b| b.to_string()),
+               after:after.to_string()}
+  };
+  record(&evictDir, vec!(change("body", Some("old"), "new"), change("event", None, "created"))).unwrap();
+  assert_eq!(operation_files(&evictDir).len(), 1);
+
+  write("body", "edited");
+  assert!(undo(&evictDir, 1).is_err());
+  assert_eq!(read_journal(&evictDir).len(), 1);
+
+  write("body", "new");
+  assert_eq!(undo(&evictDir, 1).map(|ops| ops.len()), Ok(1));
+  assert_eq!(file_util::read_string_from_path(issueDir.join("body").as_path()).ok(),
+             Some("old".to_string()));
+  assert!(!issueDir.join("event").exists());
+  assert_eq!(read_journal(&evictDir).len(), 0);
+  let _ = fs::remove_dir_all(&evictDir);
+}
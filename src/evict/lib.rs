@@ -0,0 +1,62 @@
+#![allow(non_snake_case)]
+
+#![feature(libc)]
+#![feature(collections)]
+#![feature(custom_derive, plugin)]
+
+#![feature(proc_macro)]
+
+//!The evict issue tracker as a library.  Repository is the entry
+//!point: it finds an evict directory, reads and queries its issues
+//!and makes changes under the repository lock.  The evict binary
+//!is built on top of this crate.
+
+#[macro_use]
+extern crate serde_derive;
+
+extern crate collections;
+extern crate time;
+extern crate serde;
+extern crate serde_json;
+extern crate libc;
+#[macro_use]
+extern crate error_type;
+
+pub mod issue;
+pub mod author;
+pub mod file_manager;
+pub mod file_util;
+pub mod vcs_status;
+pub mod merge;
+pub mod selection;
+pub mod config;
+pub mod status_storage;
+pub mod date_sort;
+pub mod serdetime;
+pub mod priority;
+pub mod milestone_storage;
+pub mod links;
+pub mod custom_fields;
+pub mod templates;
+pub mod markdown;
+pub mod repo_lock;
+pub mod issue_cache;
+pub mod repository;
+pub mod hooks;
+pub mod journal;
+pub mod query;
+
+pub use repository::{Repository, RepositoryError, Transaction};
+pub use issue::{Issue, IssueTimelineEvent};
+
+/*
+pub mod source{
+  pub mod parse;
+  pub mod file_parser;
+  pub mod recursive_parser;
+}
+*/
+
+pub mod evict{
+  pub static CURRENT_VERSION:usize = 1;
+}
@@ -0,0 +1,194 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use issue::Issue;
+use status_storage;
+use status_storage::StatusOption;
+
+use std::collections::HashMap;
+
+///The kinds of links that can be made between issues.  Each link
+///is stored on its source issue, and shows up on its target issue
+///under the reverse name.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LinkType{
+  Blocks,
+  DependsOn,
+  Duplicates,
+  RelatesTo
+}
+
+pub static ALL_LINK_TYPES:[LinkType; 4] = [LinkType::Blocks, LinkType::DependsOn,
+                                           LinkType::Duplicates, LinkType::RelatesTo];
+
+impl LinkType{
+  pub fn from_str(name:&str) -> Option<LinkType> {
+    ALL_LINK_TYPES.iter().find(|x| x.as_str() == name).map(|x| *x)
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match *self {
+      LinkType::Blocks => "blocks",
+      LinkType::DependsOn => "depends-on",
+      LinkType::Duplicates => "duplicates",
+      LinkType::RelatesTo => "relates-to"
+    }
+  }
+
+  ///The name of this link as seen from the target issue.
+  pub fn reverse_name(&self) -> &'static str {
+    match *self {
+      LinkType::Blocks => "blocked-by",
+      LinkType::DependsOn => "required-by",
+      LinkType::Duplicates => "duplicated-by",
+      LinkType::RelatesTo => "relates-to"
+    }
+  }
+}
+
+///Fills in the reverse_links of every issue from the links
+///stored on the other issues.
+pub fn compute_reverse_links(issues:&mut [Issue]) {
+  let mut reverse:HashMap<String, Vec<(String, String)>> = HashMap::new();
+  for issue in issues.iter() {
+    for (link_type, target) in issue.links().into_iter() {
+      if let Some(lt) = LinkType::from_str(link_type.as_str()) {
+        reverse.entry(target).or_insert(vec!())
+               .push((lt.reverse_name().to_string(), issue.id().to_string()));
+      }
+    }
+  }
+  for issue in issues.iter_mut() {
+    issue.reverse_links = reverse.remove(issue.id()).unwrap_or(vec!());
+  }
+}
+
+///Returns the IDs of all issues that must be finished before
+///this one: those it depends on, and those which block it.
+pub fn blocker_ids(issue:&Issue) -> Vec<String> {
+  let mut blockers:Vec<String> = issue.links().into_iter()
+    .filter(|&(ref link_type, _)| link_type.as_str() == LinkType::DependsOn.as_str())
+    .map(|(_, target)| target)
+    .collect();
+  for &(ref reverse_type, ref source) in issue.reverse_links.iter() {
+    if reverse_type.as_str() == LinkType::Blocks.reverse_name() && !blockers.contains(source) {
+      blockers.push(source.clone());
+    }
+  }
+  blockers
+}
+
+///Returns the issues which block this one and are not yet closed.
+///Blockers which can't be found in `all` are ignored.
+pub fn open_blockers<'x>(issue:&Issue, all:&'x [Issue], options:&[StatusOption])
+  -> Vec<&'x Issue> {
+  let ids = blocker_ids(issue);
+  all.iter().filter(|other| {
+    ids.iter().any(|id| id.as_str() == other.id()) &&
+      !status_storage::category_of(other.status.name.as_str(), options).is_closed()
+  }).collect()
+}
+
+///Finds circular blocking chains among `issues`, where each issue in
+///a chain can't be finished until the next one is.  Each chain is
+///returned as a list of issue IDs, starting and ending with the
+///same issue.  Blockers outside of `issues` are ignored.
+pub fn blocking_cycles(issues:&[Issue]) -> Vec<Vec<String>> {
+  let mut waits_on:HashMap<String, Vec<String>> = HashMap::new();
+  for issue in issues.iter() {
+    let known = blocker_ids(issue).into_iter()
+                                  .filter(|id| issues.iter().any(|i| i.id() == id.as_str()))
+                                  .collect();
+    waits_on.insert(issue.id().to_string(), known);
+  }
+
+  let mut finished:Vec<String> = vec!();
+  let mut cycles:Vec<Vec<String>> = vec!();
+  for issue in issues.iter() {
+    let mut path:Vec<String> = vec!();
+    find_cycles_from(issue.id(), &waits_on, &mut path, &mut finished, &mut cycles);
+  }
+  cycles
+}
+
+fn find_cycles_from(id:&str, waits_on:&HashMap<String, Vec<String>>, path:&mut Vec<String>,
+                    finished:&mut Vec<String>, cycles:&mut Vec<Vec<String>>) {
+  if finished.iter().any(|x| x.as_str() == id) {
+    return;
+  }
+  if let Some(pos) = path.iter().position(|x| x.as_str() == id) {
+    let mut cycle:Vec<String> = path[pos ..].to_vec();
+    cycle.push(id.to_string());
+    cycles.push(cycle);
+    return;
+  }
+  path.push(id.to_string());
+  for next in waits_on.get(id).map(|x| x.as_slice()).unwrap_or(&[]).iter() {
+    find_cycles_from(next.as_str(), waits_on, path, finished, cycles);
+  }
+  path.pop();
+  finished.push(id.to_string());
+}
+
+#[test]
+pub fn reverse_links_and_blockers(){
+  use author::Author;
+  use issue::IssueLink;
+
+  let mut blocker = Issue::new("A".to_string(), "".to_string(), Author::parse("X"));
+  let dependency = Issue::new("B".to_string(), "".to_string(), Author::parse("X"));
+  let mut blocked = Issue::new("C".to_string(), "".to_string(), Author::parse("X"));
+  blocked.base.id = "blocked".to_string();
+  blocker.add_link(IssueLink::new("blocks".to_string(), blocked.id().to_string(),
+                                  Author::parse("X"), true));
+  blocked.add_link(IssueLink::new("depends-on".to_string(), dependency.id().to_string(),
+                                  Author::parse("X"), true));
+  let mut issues = vec!(blocker, dependency, blocked);
+  compute_reverse_links(issues.as_mut_slice());
+
+  assert_eq!(issues[2].reverse_links,
+             vec!(("blocked-by".to_string(), issues[0].id().to_string())));
+  assert_eq!(issues[1].reverse_links,
+             vec!(("required-by".to_string(), "blocked".to_string())));
+  let blockers = blocker_ids(&issues[2]);
+  assert_eq!(blockers.len(), 2);
+  assert!(blockers.contains(&issues[0].id().to_string()));
+  assert!(blockers.contains(&issues[1].id().to_string()));
+}
+
+#[test]
+pub fn detect_blocking_cycle(){
+  use author::Author;
+  use issue::IssueLink;
+
+  let mut first = Issue::new("A".to_string(), "".to_string(), Author::parse("X"));
+  let mut second = Issue::new("B".to_string(), "".to_string(), Author::parse("X"));
+  first.base.id = "first".to_string();
+  second.base.id = "second".to_string();
+  first.add_link(IssueLink::new("blocks".to_string(), "second".to_string(),
+                                Author::parse("X"), true));
+  second.add_link(IssueLink::new("blocks".to_string(), "first".to_string(),
+                                 Author::parse("X"), true));
+  let mut issues = vec!(first, second);
+  compute_reverse_links(issues.as_mut_slice());
+
+  let cycles = blocking_cycles(issues.as_slice());
+  assert_eq!(cycles.len(), 1);
+  assert_eq!(cycles[0].len(), 3);
+  assert_eq!(cycles[0].first(), cycles[0].last());
+}
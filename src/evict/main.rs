@@ -1,58 +1,26 @@
 #![allow(non_snake_case)]
 
-#![feature(libc)]
-#![feature(collections)]
-#![feature(custom_derive, plugin)]
-
-#![feature(proc_macro)]
-
-
-#[macro_use]
-extern crate serde_derive;
-
-extern crate genfsm as fsm;
-extern crate collections;
 extern crate time;
-extern crate serde;
 extern crate serde_json;
-extern crate libc;
-#[macro_use]
-extern crate error_type;
+extern crate evict;
 
-pub mod issue;
-pub mod file_manager;
 pub mod commands;
-pub mod file_util;
-pub mod vcs_status;
-pub mod merge;
-pub mod selection;
-pub mod config;
-pub mod status_storage;
-pub mod date_sort;
-pub mod serdetime;
-
-/*
-pub mod source{
-  pub mod parse;
-  pub mod file_parser;
-  pub mod recursive_parser;
-}
-*/
-
-pub mod evict{
-  pub static CURRENT_VERSION:usize = 1;
-}
 
 #[cfg(not(test))]
 fn main(){
   let args = std::env::args().collect::<Vec<String>>();
   if args.len() < 2 {
     // < 2 because the first arg is the name of the binary
-    println!("No command given");
+    println!("No command given.  Run evict help for a list of commands.");
   }else{
     let cmd_args = args.iter().skip(2).map(|s_ref| s_ref.to_string()).collect();
 
-    let cmd = &args[1];
+    let cmd = if args[1].as_str() == "--help" || args[1].as_str() == "-h" {
+      "help".to_string()
+    }else{
+      args[1].clone()
+    };
+    let cmd = &cmd;
     commands::execute_command(cmd, &commands::standard_commands(), cmd_args);
   }
 }
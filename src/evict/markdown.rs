@@ -0,0 +1,423 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::env;
+use std::iter::repeat;
+#[cfg(unix)]
+use libc;
+
+static RESET:&'static str = "\x1b[0m";
+static BOLD:&'static str = "\x1b[1m";
+static HEADING:&'static str = "\x1b[1;4m";
+static ITALIC:&'static str = "\x1b[3m";
+static UNDERLINE:&'static str = "\x1b[4m";
+static CODE:&'static str = "\x1b[36m";
+static DIM:&'static str = "\x1b[2m";
+static KEYWORD:&'static str = "\x1b[35m";
+static STRING:&'static str = "\x1b[32m";
+static NUMBER:&'static str = "\x1b[33m";
+
+static DEFAULT_WIDTH:usize = 80;
+static ESCAPABLE:&'static str = "\\`*_[]()#+-.!>";
+
+///Checks whether output may use terminal colors.  Colors are on
+///when stdout is a terminal, unless NO_COLOR is set or the terminal
+///is `dumb`.
+pub fn color_enabled() -> bool {
+  stdout_is_terminal() && env::var("NO_COLOR").is_err() &&
+    env::var("TERM").map(|term| term.as_str() != "dumb").unwrap_or(true)
+}
+
+///The width to wrap text to: the COLUMNS environment variable if it
+///is set, then the width of the terminal on stdout, and 80 otherwise.
+pub fn terminal_width() -> usize {
+  let columns = env::var("COLUMNS").ok()
+                                   .and_then(|cols| usize::from_str_radix(cols.trim(), 10).ok())
+                                   .or_else(stdout_columns);
+  match columns {
+    //anything narrower than this is more likely a mistake than a terminal
+    Some(width) if width >= 20 => width,
+    _ => DEFAULT_WIDTH
+  }
+}
+
+#[cfg(unix)]
+fn stdout_is_terminal() -> bool {
+  unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stdout_is_terminal() -> bool {
+  true
+}
+
+#[cfg(unix)]
+fn stdout_columns() -> Option<usize> {
+  let mut size = libc::winsize{ws_row:0, ws_col:0, ws_xpixel:0, ws_ypixel:0};
+  match unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } {
+    0 if size.ws_col > 0 => Some(size.ws_col as usize),
+    _ => None
+  }
+}
+
+#[cfg(not(unix))]
+fn stdout_columns() -> Option<usize> {
+  None
+}
+
+///Renders Markdown text for the terminal, wrapped to `width`.
+///Headings, emphasis, inline code, links, lists, block quotes and
+///fenced code blocks are supported; anything else is treated as
+///paragraph text.  Without `color`, the Markdown markers are still
+///removed but no ANSI codes are added.
+pub fn render(text:&str, width:usize, color:bool) -> String {
+  let mut output:Vec<String> = vec!();
+  let mut paragraph:Vec<&str> = vec!();
+  let mut codeLang:Option<String> = None;
+
+  for line in text.lines() {
+    let trimmed = line.trim();
+    if codeLang.is_some() {
+      if trimmed.starts_with("```") {
+        codeLang = None;
+      }else{
+        let lang = codeLang.as_ref().unwrap().as_str();
+        output.push(format!("    {}", highlight_code(line, lang, color)));
+      }
+      continue;
+    }
+    if trimmed.starts_with("```") {
+      flush_paragraph(&mut paragraph, &mut output, width, color);
+      codeLang = Some(trimmed[3 ..].trim().to_lowercase());
+    }else if trimmed.len() == 0 {
+      flush_paragraph(&mut paragraph, &mut output, width, color);
+      output.push("".to_string());
+    }else if let Some((level, title)) = heading(trimmed) {
+      flush_paragraph(&mut paragraph, &mut output, width, color);
+      let style = if level == 1 {HEADING} else {BOLD};
+      let rendered = render_inline(title, color);
+      output.push(if color {format!("{}{}{}", style, rendered, RESET)} else {rendered});
+    }else if let Some((marker, item)) = list_item(trimmed) {
+      flush_paragraph(&mut paragraph, &mut output, width, color);
+      let indent = line.len() - line.trim_left().len();
+      let prefix = format!("{}{} ", spaces(indent), marker);
+      let hanging = spaces(prefix.chars().count());
+      output.extend(wrap(render_inline(item, color).as_str(), width, prefix.as_str(),
+                         hanging.as_str()));
+    }else if trimmed.starts_with('>') {
+      flush_paragraph(&mut paragraph, &mut output, width, color);
+      let prefix = if color {format!("{}│{} ", DIM, RESET)} else {"│ ".to_string()};
+      output.extend(wrap(render_inline(trimmed[1 ..].trim(), color).as_str(), width,
+                         prefix.as_str(), prefix.as_str()));
+    }else{
+      paragraph.push(trimmed);
+    }
+  }
+  flush_paragraph(&mut paragraph, &mut output, width, color);
+  while output.last().map_or(false, |line| line.len() == 0) {
+    output.pop();
+  }
+  output.join("\n")
+}
+
+///Formats an issue body or comment for display, indented by
+///`indent` spaces.  Unless `raw` is set, the text is rendered as
+///Markdown and wrapped to fit the terminal.
+pub fn format_text(text:&str, indent:usize, raw:bool) -> String {
+  let rendered = if raw {
+    text.to_string()
+  }else{
+    let width = terminal_width();
+    render(text, if width > indent + 20 {width - indent} else {20}, color_enabled())
+  };
+  let prefix = spaces(indent);
+  rendered.lines().map(|line| format!("{}{}", prefix, line)).collect::<Vec<String>>().join("\n")
+}
+
+fn flush_paragraph(paragraph:&mut Vec<&str>, output:&mut Vec<String>, width:usize, color:bool) {
+  if paragraph.len() > 0 {
+    let text = render_inline(paragraph.join(" ").as_str(), color);
+    output.extend(wrap(text.as_str(), width, "", ""));
+    paragraph.clear();
+  }
+}
+
+fn spaces(count:usize) -> String {
+  repeat(" ").take(count).collect()
+}
+
+fn heading(line:&str) -> Option<(usize, &str)> {
+  let level = line.chars().take_while(|c| *c == '#').count();
+  if level > 0 && level <= 6 && line[level ..].starts_with(' ') {
+    Some((level, line[level ..].trim()))
+  }else{
+    None
+  }
+}
+
+///Splits a list item into the marker to print and the item text.
+fn list_item(line:&str) -> Option<(String, &str)> {
+  if line.starts_with("- ") || line.starts_with("* ") || line.starts_with("+ ") {
+    return Some(("•".to_string(), line[2 ..].trim()));
+  }
+  let digits = line.chars().take_while(|c| c.is_digit(10)).count();
+  if digits > 0 && (line[digits ..].starts_with(". ") || line[digits ..].starts_with(") ")) {
+    Some((line[.. digits + 1].to_string(), line[digits + 2 ..].trim()))
+  }else{
+    None
+  }
+}
+
+///Renders emphasis, inline code and links within a line.
+fn render_inline(text:&str, color:bool) -> String {
+  let chars:Vec<char> = text.chars().collect();
+  let mut output = String::new();
+  let mut bold = false;
+  let mut italic = false;
+  let mut index = 0;
+  while index < chars.len() {
+    let c = chars[index];
+    let next = chars.get(index + 1).cloned();
+    if c == '\\' && next.map_or(false, |n| ESCAPABLE.contains(n)) {
+      output.push(next.unwrap());
+      index += 2;
+    }else if c == '`' {
+      match find_char(&chars, index + 1, '`') {
+        Some(end) => {
+          let code:String = chars[index + 1 .. end].iter().cloned().collect();
+          output.push_str(styled(code.as_str(), CODE, color).as_str());
+          index = end + 1;
+        }
+        None => {output.push(c); index += 1;}
+      }
+    }else if (c == '*' || c == '_') && next == Some(c) {
+      bold = !bold;
+      output.push_str(restyle(bold, italic, color).as_str());
+      index += 2;
+    }else if (c == '*' || c == '_') && is_emphasis_marker(&chars, index, italic) {
+      italic = !italic;
+      output.push_str(restyle(bold, italic, color).as_str());
+      index += 1;
+    }else if c == '[' {
+      match parse_link(&chars, index) {
+        Some((label, url, end)) => {
+          output.push_str(styled(label.as_str(), UNDERLINE, color).as_str());
+          if url != label {
+            output.push_str(styled(format!(" ({})", url).as_str(), DIM, color).as_str());
+          }
+          output.push_str(restyle(bold, italic, color).as_str());
+          index = end;
+        }
+        None => {output.push(c); index += 1;}
+      }
+    }else{
+      output.push(c);
+      index += 1;
+    }
+  }
+  if color && (bold || italic) {
+    output.push_str(RESET);
+  }
+  output
+}
+
+///An emphasis marker opens at the start of a word and closes at
+///the end of one, so underscores inside names are left alone.
+fn is_emphasis_marker(chars:&[char], index:usize, open:bool) -> bool {
+  let before = if index == 0 {None} else {Some(chars[index - 1])};
+  let after = chars.get(index + 1).cloned();
+  if open {
+    before.map_or(false, |b| !b.is_whitespace()) && after.map_or(true, |a| !a.is_alphanumeric())
+  }else{
+    before.map_or(true, |b| !b.is_alphanumeric()) && after.map_or(false, |a| !a.is_whitespace())
+  }
+}
+
+fn find_char(chars:&[char], from:usize, target:char) -> Option<usize> {
+  (from .. chars.len()).find(|&i| chars[i] == target)
+}
+
+///Parses `[label](url)` starting at `start`, returning the label,
+///the url and the index just after the link.
+fn parse_link(chars:&[char], start:usize) -> Option<(String, String, usize)> {
+  let closeLabel = match find_char(chars, start + 1, ']') {
+    Some(close) if chars.get(close + 1) == Some(&'(') => close,
+    _ => return None
+  };
+  let closeUrl = match find_char(chars, closeLabel + 2, ')') {
+    Some(close) => close,
+    None => return None
+  };
+  Some((chars[start + 1 .. closeLabel].iter().cloned().collect(),
+        chars[closeLabel + 2 .. closeUrl].iter().cloned().collect(),
+        closeUrl + 1))
+}
+
+fn styled(text:&str, style:&str, color:bool) -> String {
+  if color {format!("{}{}{}", style, text, RESET)} else {text.to_string()}
+}
+
+///Resets the style, then turns back on whatever emphasis is active.
+fn restyle(bold:bool, italic:bool, color:bool) -> String {
+  if !color {
+    return "".to_string();
+  }
+  let mut codes = RESET.to_string();
+  if bold {codes.push_str(BOLD);}
+  if italic {codes.push_str(ITALIC);}
+  codes
+}
+
+///The number of characters of `text` which take up space on the
+///terminal, not counting ANSI escape codes.
+fn visible_len(text:&str) -> usize {
+  let mut count = 0;
+  let mut inEscape = false;
+  for c in text.chars() {
+    if inEscape {
+      inEscape = c != 'm';
+    }else if c == '\x1b' {
+      inEscape = true;
+    }else{
+      count += 1;
+    }
+  }
+  count
+}
+
+///Wraps text into lines no wider than `width`, starting the first
+///line with `first` and the others with `rest`.  Words longer than
+///a line are left whole.
+fn wrap(text:&str, width:usize, first:&str, rest:&str) -> Vec<String> {
+  let mut lines:Vec<String> = vec!();
+  let mut current = first.to_string();
+  let mut currentLen = visible_len(first);
+  let mut empty = true;
+  for word in text.split_whitespace() {
+    let wordLen = visible_len(word);
+    if !empty && currentLen + 1 + wordLen > width {
+      lines.push(current);
+      current = rest.to_string();
+      currentLen = visible_len(rest);
+      empty = true;
+    }
+    if !empty {
+      current.push(' ');
+      currentLen += 1;
+    }
+    current.push_str(word);
+    currentLen += wordLen;
+    empty = false;
+  }
+  lines.push(current);
+  lines
+}
+
+fn comment_marker(lang:&str) -> Option<&'static str> {
+  match lang {
+    "rust" | "rs" | "c" | "cpp" | "c++" | "java" | "js" | "javascript" | "go" => Some("//"),
+    "python" | "py" | "sh" | "bash" | "shell" | "ruby" | "rb" => Some("#"),
+    _ => None
+  }
+}
+
+fn keywords(lang:&str) -> &'static [&'static str] {
+  static RUST:&'static [&'static str] = &["as", "break", "const", "continue", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "mut",
+    "pub", "ref", "return", "self", "Self", "static", "struct", "trait", "true", "type", "use",
+    "where", "while"];
+  static C_LIKE:&'static [&'static str] = &["break", "case", "class", "const", "continue",
+    "else", "false", "for", "function", "if", "int", "new", "null", "return", "static",
+    "struct", "switch", "this", "true", "var", "void", "while"];
+  static PYTHON:&'static [&'static str] = &["and", "class", "def", "elif", "else", "False",
+    "for", "from", "if", "import", "in", "is", "None", "not", "or", "return", "self", "True",
+    "while", "with"];
+  static SHELL:&'static [&'static str] = &["case", "do", "done", "echo", "elif", "else", "esac",
+    "export", "fi", "for", "function", "if", "in", "then", "while"];
+  match lang {
+    "rust" | "rs" => RUST,
+    "c" | "cpp" | "c++" | "java" | "js" | "javascript" | "go" => C_LIKE,
+    "python" | "py" | "ruby" | "rb" => PYTHON,
+    "sh" | "bash" | "shell" => SHELL,
+    _ => &[]
+  }
+}
+
+///Colors keywords, strings, numbers and comments in a line of a
+///code block.  Languages which aren't known are shown in a single
+///color.
+fn highlight_code(line:&str, lang:&str, color:bool) -> String {
+  if !color {
+    return line.to_string();
+  }
+  let marker = match comment_marker(lang) {
+    Some(marker) => marker,
+    None => return styled(line, CODE, true)
+  };
+  let words = keywords(lang);
+  let chars:Vec<char> = line.chars().collect();
+  let mut output = String::new();
+  let mut index = 0;
+  while index < chars.len() {
+    let c = chars[index];
+    let rest:String = chars[index ..].iter().cloned().collect();
+    if rest.starts_with(marker) {
+      output.push_str(styled(rest.as_str(), DIM, true).as_str());
+      break;
+    }else if c == '"' {
+      let end = find_char(&chars, index + 1, '"').map_or(chars.len(), |end| end + 1);
+      let literal:String = chars[index .. end].iter().cloned().collect();
+      output.push_str(styled(literal.as_str(), STRING, true).as_str());
+      index = end;
+    }else if c.is_alphanumeric() || c == '_' {
+      let end = (index .. chars.len()).find(|&i| !(chars[i].is_alphanumeric() || chars[i] == '_'))
+                                      .unwrap_or(chars.len());
+      let word:String = chars[index .. end].iter().cloned().collect();
+      if words.contains(&word.as_str()) {
+        output.push_str(styled(word.as_str(), KEYWORD, true).as_str());
+      }else if c.is_digit(10) {
+        output.push_str(styled(word.as_str(), NUMBER, true).as_str());
+      }else{
+        output.push_str(word.as_str());
+      }
+      index = end;
+    }else{
+      output.push(c);
+      index += 1;
+    }
+  }
+  output
+}
+
+#[test]
+pub fn render_plain_markdown(){
+  let text = "# Title\n\nSome *emphasised* and **bold** text with `code`\n\
+              and a [link](http://example.com).\n\n- one\n- two\n\n```\nlet x = 1;\n```";
+  let rendered = render(text, 80, false);
+  assert_eq!(rendered, "Title\n\nSome emphasised and bold text with code and a link \
+                        (http://example.com).\n\n• one\n• two\n\n    let x = 1;".to_string());
+  //underscores in names aren't emphasis
+  assert_eq!(render("use snake_case_names", 80, false), "use snake_case_names".to_string());
+}
+
+#[test]
+pub fn render_wraps_to_width(){
+  let rendered = render("- aaa bbb ccc ddd", 10, false);
+  assert_eq!(rendered, "• aaa bbb\n  ccc ddd".to_string());
+  assert_eq!(visible_len("\x1b[1mbold\x1b[0m"), 4);
+}
@@ -0,0 +1,159 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use file_util;
+use file_manager;
+use serdetime::SerdeTime;
+use time;
+use serde_json;
+use serde_json::Result as SerdeResult;
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+#[cfg(test)]
+use std::env;
+#[cfg(test)]
+use std::fs;
+
+static MILESTONE_FILE:&'static str = "milestones";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Milestone{
+  pub name:String,
+  pub description:String,
+  pub due_date:Option<SerdeTime>,
+  pub closed:bool
+}
+
+impl Milestone{
+  pub fn new(name:String, description:String, due_date:Option<SerdeTime>) -> Milestone {
+    Milestone{name:name, description:description, due_date:due_date, closed:false}
+  }
+
+  ///A milestone is overdue if it is still open after the end of its due date.
+  pub fn is_overdue(&self) -> bool {
+    match self.due_date {
+      Some(ref due) => {
+        let endOfDue = due.0.to_timespec() + time::Duration::days(1);
+        !self.closed && endOfDue < time::now().to_timespec()
+      }
+      None => false
+    }
+  }
+}
+
+fn milestone_path(evictDir:&Path) -> PathBuf {
+  evictDir.join(MILESTONE_FILE)
+}
+
+///Reads the milestones.  A missing file means there are none, but a
+///file which can't be read or parsed is an error, so that writing
+///the milestones back doesn't lose the ones in it.
+pub fn read_milestones() -> SerdeResult<Vec<Milestone>> {
+  read_milestones_in(Path::new(file_manager::EVICT_DIRECTORY))
+}
+
+pub fn read_milestones_in(evictDir:&Path) -> SerdeResult<Vec<Milestone>> {
+  match file_util::read_string_from_path(milestone_path(evictDir).as_path()) {
+    Ok(data) => serde_json::from_str(data.as_str()),
+    Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(vec!()),
+    Err(e) => Err(e.into())
+  }
+}
+
+pub fn write_milestones(milestones:&[Milestone]) -> SerdeResult<()> {
+  write_milestones_in(Path::new(file_manager::EVICT_DIRECTORY), milestones)
+}
+
+pub fn write_milestones_in(evictDir:&Path, milestones:&[Milestone]) -> SerdeResult<()> {
+  file_manager::write_json(milestone_path(evictDir).to_string_lossy().as_ref(), &milestones)
+}
+
+pub fn find_milestone(name:&str) -> SerdeResult<Option<Milestone>> {
+  read_milestones().map(|milestones| milestones.into_iter().find(|m| m.name.as_str() == name))
+}
+
+///Adds `milestone`, unless there is one with the same name.
+pub fn add_milestone(milestones:&mut Vec<Milestone>, milestone:Milestone) -> Result<(), String> {
+  if milestones.iter().any(|m| m.name == milestone.name) {
+    return Err(format!("Milestone {} already exists", milestone.name));
+  }
+  milestones.push(milestone);
+  Ok(())
+}
+
+pub fn close_milestone(milestones:&mut [Milestone], name:&str) -> Result<(), String> {
+  match milestones.iter_mut().find(|m| m.name.as_str() == name) {
+    Some(milestone) => {
+      milestone.closed = true;
+      Ok(())
+    }
+    None => Err(format!("No milestone named {}", name))
+  }
+}
+
+///The name of the milestone called `name`, if issues can be
+///assigned to it: it must exist and be open.
+pub fn assignable_milestone(milestones:&[Milestone], name:&str) -> Result<String, String> {
+  match milestones.iter().find(|m| m.name.as_str() == name) {
+    Some(milestone) if milestone.closed => Err(format!("Milestone {} is closed", name)),
+    Some(milestone) => Ok(milestone.name.clone()),
+    None => Err(format!("No milestone named {}", name))
+  }
+}
+
+#[cfg(test)]
+fn test_dir(name:&str) -> PathBuf {
+  let dir = env::temp_dir().join(format!("evict-milestone-test-{}-{}", name, time::precise_time_ns()));
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+#[test]
+pub fn create_close_and_assign(){
+  let dir = test_dir("create");
+  let mut milestones = read_milestones_in(&dir).unwrap();
+  assert_eq!(milestones.len(), 0);
+  add_milestone(&mut milestones, Milestone::new("1.0".to_string(), "".to_string(), None)).unwrap();
+  add_milestone(&mut milestones, Milestone::new("2.0".to_string(), "Later".to_string(), None)).unwrap();
+  assert!(add_milestone(&mut milestones, Milestone::new("1.0".to_string(), "".to_string(), None))
+            .is_err());
+  write_milestones_in(&dir, milestones.as_slice()).unwrap();
+
+  let mut milestones = read_milestones_in(&dir).unwrap();
+  assert_eq!(milestones.len(), 2);
+  assert_eq!(assignable_milestone(milestones.as_slice(), "1.0"), Ok("1.0".to_string()));
+  close_milestone(milestones.as_mut_slice(), "1.0").unwrap();
+  assert!(close_milestone(milestones.as_mut_slice(), "3.0").is_err());
+  write_milestones_in(&dir, milestones.as_slice()).unwrap();
+
+  let milestones = read_milestones_in(&dir).unwrap();
+  assert!(milestones[0].closed && !milestones[1].closed);
+  assert!(assignable_milestone(milestones.as_slice(), "1.0").is_err());
+  assert!(assignable_milestone(milestones.as_slice(), "3.0").is_err());
+  assert_eq!(assignable_milestone(milestones.as_slice(), "2.0"), Ok("2.0".to_string()));
+  let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn unreadable_milestones_are_an_error(){
+  let dir = test_dir("corrupt");
+  file_util::write_atomically(milestone_path(&dir).as_path(), b"[{\"name\": ").unwrap();
+  assert!(read_milestones_in(&dir).is_err());
+  let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,82 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use config;
+
+static DEFAULT_PRIORITIES:[&'static str; 4] = ["low", "normal", "high", "critical"];
+
+///The ordered scale of priorities an issue can have, from
+///lowest to highest.  Configured by the `priorities` list in
+///the repo config, with a default scale if that is empty.
+pub struct PriorityScale{
+  levels:Vec<String>
+}
+
+impl PriorityScale{
+  pub fn load() -> PriorityScale {
+    let conf = config::Config::load();
+    if conf.priorities.len() == 0 {
+      PriorityScale::default()
+    }else{
+      PriorityScale{levels:conf.priorities}
+    }
+  }
+
+  pub fn default() -> PriorityScale {
+    PriorityScale{levels:DEFAULT_PRIORITIES.iter().map(|x| x.to_string()).collect()}
+  }
+
+  pub fn levels(&self) -> &[String] {
+    self.levels.as_slice()
+  }
+
+  ///Finds a priority level by name or by its index in the scale.
+  pub fn resolve(&self, ident:&str) -> Option<&str> {
+    match usize::from_str_radix(ident, 10) {
+      Ok(index) => self.levels.get(index),
+      _ => self.levels.iter().find(|x| x.as_str() == ident)
+    }.map(|x| x.as_str())
+  }
+
+  ///The position of a level in the scale, where higher is more
+  ///important.  Levels which are no longer in the scale have no rank.
+  pub fn rank(&self, level:&str) -> Option<usize> {
+    self.levels.iter().position(|x| x.as_str() == level)
+  }
+
+  ///The terminal color code used to display a priority level.  The
+  ///top level is red and the next is yellow, everything else is
+  ///displayed in the default color.
+  pub fn color(&self, level:&str) -> &'static str {
+    match self.rank(level) {
+      Some(rank) if rank + 1 == self.levels.len() => "\x1b[31m",
+      Some(rank) if rank + 2 == self.levels.len() => "\x1b[33m",
+      _ => "\x1b[0m"
+    }
+  }
+}
+
+#[test]
+pub fn resolve_and_rank(){
+  let scale = PriorityScale::default();
+  assert_eq!(scale.resolve("high"), Some("high"));
+  assert_eq!(scale.resolve("0"), Some("low"));
+  assert_eq!(scale.resolve("urgent"), None);
+  assert!(scale.rank("critical") > scale.rank("normal"));
+  assert_eq!(scale.rank("urgent"), None);
+}
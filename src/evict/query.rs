@@ -0,0 +1,217 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use issue::Issue;
+use author::Author;
+
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+///A filter on issues, as given to `--where`: terms like
+///`tag:sprint-3` or `status:open` combined with `and`, `or`, `not`
+///and parentheses.  `and` binds tighter than `or`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Query{
+  Term(Field, String),
+  Not(Box<Query>),
+  And(Box<Query>, Box<Query>),
+  Or(Box<Query>, Box<Query>)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Field{
+  Id,
+  Tag,
+  Status,
+  Author,
+  ///`assignee:me` is the current author and `assignee:none` unassigned issues.
+  Assignee,
+  Priority,
+  Milestone,
+  ///Matches titles containing the value, ignoring case.
+  Title
+}
+
+impl Field{
+  fn from_str(name:&str) -> Option<Field> {
+    match name {
+      "id" => Some(Field::Id),
+      "tag" => Some(Field::Tag),
+      "status" => Some(Field::Status),
+      "author" => Some(Field::Author),
+      "assignee" => Some(Field::Assignee),
+      "priority" => Some(Field::Priority),
+      "milestone" => Some(Field::Milestone),
+      "title" => Some(Field::Title),
+      _ => None
+    }
+  }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token{
+  Word(String),
+  Open,
+  Close
+}
+
+///Splits a query into words and parentheses.  Quotes group words
+///containing spaces, as in `title:"crash on start"`.
+fn tokenize(text:&str) -> Result<Vec<Token>, String> {
+  let mut tokens = vec!();
+  let mut current = String::new();
+  let mut quoted = false;
+  for c in text.chars() {
+    if quoted {
+      if c == '"' {quoted = false} else {current.push(c)}
+    }else if c == '"' {
+      quoted = true;
+    }else if c.is_whitespace() || c == '(' || c == ')' {
+      if current.len() > 0 {
+        tokens.push(Token::Word(current.clone()));
+        current.clear();
+      }
+      if c == '(' {tokens.push(Token::Open)}
+      if c == ')' {tokens.push(Token::Close)}
+    }else{
+      current.push(c);
+    }
+  }
+  if quoted {
+    return Err("Unclosed quote in query".to_string());
+  }
+  if current.len() > 0 {
+    tokens.push(Token::Word(current));
+  }
+  Ok(tokens)
+}
+
+type Tokens = Peekable<IntoIter<Token>>;
+
+impl Query{
+  pub fn parse(text:&str) -> Result<Query, String> {
+    let mut tokens = try!(tokenize(text)).into_iter().peekable();
+    let query = try!(parse_or(&mut tokens));
+    match tokens.next() {
+      None => Ok(query),
+      Some(Token::Close) => Err("Unmatched ) in query".to_string()),
+      Some(Token::Open) => Err("Unexpected ( in query".to_string()),
+      Some(Token::Word(word)) => Err(format!("Expected and or or before {}", word))
+    }
+  }
+
+  ///Whether `issue` matches, where `me` is the current author.
+  pub fn matches(&self, issue:&Issue, me:Option<&Author>) -> bool {
+    match *self {
+      Query::Term(field, ref value) => term_matches(field, value.as_str(), issue, me),
+      Query::Not(ref query) => !query.matches(issue, me),
+      Query::And(ref a, ref b) => a.matches(issue, me) && b.matches(issue, me),
+      Query::Or(ref a, ref b) => a.matches(issue, me) || b.matches(issue, me)
+    }
+  }
+}
+
+fn is_word(tokens:&mut Tokens, word:&str) -> bool {
+  match tokens.peek() {
+    Some(&Token::Word(ref w)) => w.as_str() == word,
+    _ => false
+  }
+}
+
+fn parse_or(tokens:&mut Tokens) -> Result<Query, String> {
+  let mut query = try!(parse_and(tokens));
+  while is_word(tokens, "or") {
+    tokens.next();
+    let right = try!(parse_and(tokens));
+    query = Query::Or(Box::new(query), Box::new(right));
+  }
+  Ok(query)
+}
+
+fn parse_and(tokens:&mut Tokens) -> Result<Query, String> {
+  let mut query = try!(parse_not(tokens));
+  while is_word(tokens, "and") {
+    tokens.next();
+    let right = try!(parse_not(tokens));
+    query = Query::And(Box::new(query), Box::new(right));
+  }
+  Ok(query)
+}
+
+fn parse_not(tokens:&mut Tokens) -> Result<Query, String> {
+  match tokens.next() {
+    Some(Token::Word(ref word)) if word.as_str() == "not" =>
+      parse_not(tokens).map(|query| Query::Not(Box::new(query))),
+    Some(Token::Open) => {
+      let query = try!(parse_or(tokens));
+      match tokens.next() {
+        Some(Token::Close) => Ok(query),
+        _ => Err("Missing ) in query".to_string())
+      }
+    }
+    Some(Token::Word(word)) => parse_term(word.as_str()),
+    Some(Token::Close) => Err("Unexpected ) in query".to_string()),
+    None => Err("Query ends too soon".to_string())
+  }
+}
+
+fn parse_term(word:&str) -> Result<Query, String> {
+  let colon = match word.find(':') {
+    Some(colon) => colon,
+    None => return Err(format!("{} should be <field>:<value>, e.g. tag:bug", word))
+  };
+  match Field::from_str(&word[.. colon]) {
+    Some(field) => Ok(Query::Term(field, word[colon + 1 ..].to_string())),
+    None => Err(format!("Unknown field {}.  Fields are id, tag, status, author, assignee, \
+                         priority, milestone and title.", &word[.. colon]))
+  }
+}
+
+fn term_matches(field:Field, value:&str, issue:&Issue, me:Option<&Author>) -> bool {
+  match field {
+    Field::Id => issue.id().ends_with(value),
+    Field::Tag => issue.all_tags().iter().any(|tag| tag.as_str() == value),
+    Field::Status => issue.status.name.as_str() == value,
+    Field::Author => issue.author().matches(value),
+    Field::Assignee => match (issue.assignee(), value) {
+      (None, "none") => true,
+      (Some(assignee), "me") => me == Some(assignee),
+      (Some(assignee), _) => assignee.matches(value),
+      (None, _) => false
+    },
+    Field::Priority => issue.priority() == Some(value),
+    Field::Milestone => issue.milestone() == Some(value),
+    Field::Title => issue.title().to_lowercase().contains(value.to_lowercase().as_str())
+  }
+}
+
+#[test]
+pub fn parse_precedence_and_errors(){
+  let term = |field, value:&str| Query::Term(field, value.to_string());
+  assert_eq!(Query::parse("tag:a or tag:b and not status:open"),
+             Ok(Query::Or(Box::new(term(Field::Tag, "a")),
+                          Box::new(Query::And(Box::new(term(Field::Tag, "b")),
+                                              Box::new(Query::Not(Box::new(term(Field::Status, "open")))))))));
+  assert_eq!(Query::parse("(tag:a or tag:b) and title:\"two words\""),
+             Ok(Query::And(Box::new(Query::Or(Box::new(term(Field::Tag, "a")),
+                                              Box::new(term(Field::Tag, "b")))),
+                           Box::new(term(Field::Title, "two words")))));
+  assert!(Query::parse("tag:a tag:b").is_err());
+  assert!(Query::parse("colour:red").is_err());
+  assert!(Query::parse("(tag:a").is_err());
+}
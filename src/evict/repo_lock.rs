@@ -0,0 +1,165 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use file_manager;
+use file_util;
+
+use std::cell::RefCell;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(unix)]
+use libc;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+
+static LOCK_FILE:&'static str = "lock";
+///How many times to retry taking the lock, WAIT_MILLIS apart,
+///before giving up.
+static LOCK_ATTEMPTS:u32 = 100;
+static WAIT_MILLIS:u64 = 100;
+
+//the evict directories this thread holds the lock on, so that taking
+//it again, as save_changes does inside a locked command, doesn't wait
+//on itself
+thread_local!(static HELD:RefCell<Vec<PathBuf>> = RefCell::new(vec!()));
+
+///Holds the lock on the evict directory, which mutating commands
+///take so that two evict processes can't interleave their reads
+///and writes.  On unix the lock is an flock on `.evict/lock`, which
+///the OS releases when the process exits, so a killed evict never
+///leaves the repository locked.  The file itself stays, holding the
+///pid of the last process to lock it.
+///
+///The lock may be taken again while it is held; only the outermost
+///RepoLock releases it.
+pub struct RepoLock{
+  ///None for a RepoLock taken while the lock was already held.
+  file:Option<File>,
+  path:PathBuf,
+  held:PathBuf
+}
+
+impl RepoLock{
+  ///Takes the lock, waiting a few seconds for another evict process
+  ///to finish if necessary.
+  pub fn acquire() -> Result<RepoLock, String> {
+    RepoLock::acquire_in(Path::new(file_manager::EVICT_DIRECTORY))
+  }
+
+  ///Like acquire, for the evict directory `evictDir`.
+  pub fn acquire_in(evictDir:&Path) -> Result<RepoLock, String> {
+    let path = evictDir.join(LOCK_FILE);
+    let held = fs::canonicalize(evictDir).unwrap_or(evictDir.to_path_buf());
+    if HELD.with(|dirs| dirs.borrow().contains(&held)) {
+      return Ok(RepoLock{file:None, path:path, held:held});
+    }
+    for _ in 0 .. LOCK_ATTEMPTS {
+      match try_lock(&path) {
+        Ok(Some(mut file)) => {
+          let _ = file.set_len(0);
+          let _ = file.seek(SeekFrom::Start(0));
+          let _ = write!(file, "{}", process_id());
+          HELD.with(|dirs| dirs.borrow_mut().push(held.clone()));
+          return Ok(RepoLock{file:Some(file), path:path, held:held});
+        }
+        Ok(None) => thread::sleep(Duration::from_millis(WAIT_MILLIS)),
+        Err(e) => return Err(format!("Couldn't lock {}: {}", path.display(), e))
+      }
+    }
+    let holder = file_util::read_string_from_path(&path).unwrap_or("".to_string());
+    Err(format!("{} is locked by another evict process{}.",
+                path.display(),
+                if holder.trim().len() > 0 {format!(" (pid {})", holder.trim())} else {"".to_string()}))
+  }
+}
+
+///Opens and locks the lock file, or returns None if another process
+///holds the lock.
+#[cfg(unix)]
+fn try_lock(path:&Path) -> io::Result<Option<File>> {
+  let file = try!(fs::OpenOptions::new().read(true).write(true).create(true).open(path));
+  if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+    Ok(Some(file))
+  }else{
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {Ok(None)} else {Err(err)}
+  }
+}
+
+///Without flock the lock file is created exclusively, and removed
+///again when the RepoLock is dropped.
+#[cfg(not(unix))]
+fn try_lock(path:&Path) -> io::Result<Option<File>> {
+  match fs::OpenOptions::new().write(true).create_new(true).open(path) {
+    Ok(file) => Ok(Some(file)),
+    Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(None),
+    Err(e) => Err(e)
+  }
+}
+
+impl Drop for RepoLock{
+  fn drop(&mut self) {
+    if self.file.take().is_some() {
+      let held = &self.held;
+      HELD.with(|dirs| dirs.borrow_mut().retain(|dir| dir != held));
+      release(&self.path);
+    }
+  }
+}
+
+///Closing the file releases the flock.  The file is left in place,
+///as removing it could let a process waiting on the old file and one
+///creating a new file both take the lock.
+#[cfg(unix)]
+fn release(_path:&Path) {}
+
+#[cfg(not(unix))]
+fn release(path:&Path) {
+  let _ = fs::remove_file(path);
+}
+
+#[cfg(unix)]
+fn process_id() -> String {
+  unsafe { libc::getpid() }.to_string()
+}
+
+#[cfg(not(unix))]
+fn process_id() -> String {
+  "".to_string()
+}
+
+#[test]
+pub fn lock_is_exclusive_and_released_on_drop(){
+  let dir = ::std::env::temp_dir().join(format!("evict-lock-test-{}", process_id()));
+  let _ = fs::create_dir_all(&dir);
+  {
+    let _held = RepoLock::acquire_in(&dir).unwrap();
+    assert!(try_lock(&dir.join(LOCK_FILE)).unwrap().is_none());
+    //taking it again on the same thread doesn't wait
+    drop(RepoLock::acquire_in(&dir).unwrap());
+    assert!(try_lock(&dir.join(LOCK_FILE)).unwrap().is_none());
+  }
+  assert!(try_lock(&dir.join(LOCK_FILE)).unwrap().is_some());
+  let _ = fs::remove_dir_all(&dir);
+}
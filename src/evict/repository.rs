@@ -0,0 +1,304 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use file_manager;
+use file_manager::{IssueChange, IssueReader, ReadParts, DataReadError};
+use issue::{Issue, IssueTimelineEvent};
+use issue_cache;
+use issue_cache::IssueSummary;
+use author::Author;
+use links;
+use selection;
+use repo_lock::RepoLock;
+
+use serde_json::Error as JsonError;
+use std::fmt::{Display, Formatter};
+use std::fmt::Error as FmtError;
+use std::io::Error as IoError;
+use std::cell::RefCell;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum RepositoryError {
+  ///No evict directory was found at or above this path.
+  NotFound(PathBuf),
+  Io(IoError),
+  Json(JsonError),
+  Read(DataReadError),
+  ///The repository lock couldn't be taken.
+  Lock(String),
+  ///No issue matches this ID part.
+  NoMatch(String),
+  ///Several issues match this ID part; their IDs are given.
+  Ambiguous(String, Vec<String>)
+}
+
+impl Display for RepositoryError {
+  fn fmt(&self, fmt:&mut Formatter) -> Result<(), FmtError> {
+    use self::RepositoryError::*;
+    match *self {
+      NotFound(ref path) =>
+        write!(fmt, "There is no evict directory in {} or above it.  Run evict init.", path.display()),
+      Io(ref e) => write!(fmt, "{}", e),
+      Json(ref e) => write!(fmt, "{}", e),
+      Read(ref e) => write!(fmt, "{}", e),
+      Lock(ref msg) => write!(fmt, "{}", msg),
+      NoMatch(ref idPart) => write!(fmt, "No issue matches {}", idPart),
+      Ambiguous(ref idPart, ref ids) =>
+        write!(fmt, "{} matches {} issues: {}", idPart, ids.len(), ids.join(", "))
+    }
+  }
+}
+
+impl ::std::error::Error for RepositoryError {
+  fn description(&self) -> &str {
+    use self::RepositoryError::*;
+    match *self {
+      NotFound(_) => "no evict directory",
+      Io(_) => "io error",
+      Json(_) => "json error",
+      Read(_) => "issue read error",
+      Lock(_) => "repository locked",
+      NoMatch(_) => "no matching issue",
+      Ambiguous(_, _) => "more than one matching issue"
+    }
+  }
+
+  fn cause(&self) -> Option<&::std::error::Error> {
+    match *self {
+      RepositoryError::Io(ref e) => Some(e),
+      RepositoryError::Json(ref e) => Some(e),
+      RepositoryError::Read(ref e) => Some(e),
+      _ => None
+    }
+  }
+}
+
+impl From<IoError> for RepositoryError {
+  fn from(err:IoError) -> RepositoryError {
+    RepositoryError::Io(err)
+  }
+}
+
+impl From<JsonError> for RepositoryError {
+  fn from(err:JsonError) -> RepositoryError {
+    RepositoryError::Json(err)
+  }
+}
+
+impl From<DataReadError> for RepositoryError {
+  fn from(err:DataReadError) -> RepositoryError {
+    RepositoryError::Read(err)
+  }
+}
+
+pub type RepositoryResult<T> = Result<T, RepositoryError>;
+
+///A handle on the evict issues kept in a directory.  Reads go
+///straight to disk; changes are made through a Transaction, which
+///holds the repository lock and writes nothing unless it succeeds.
+///
+///Nothing is printed.  Problems after a save was written, such as a
+///failing post hook, are kept for take_warnings.
+#[derive(Clone, Debug)]
+pub struct Repository{
+  root:PathBuf,
+  warnings:RefCell<Vec<String>>
+}
+
+impl Repository{
+  ///Opens the repository whose evict directory is directly in `root`.
+  pub fn open<P:AsRef<Path>>(root:P) -> RepositoryResult<Repository> {
+    let root = root.as_ref().to_path_buf();
+    if root.join(file_manager::EVICT_DIRECTORY).is_dir() {
+      Ok(Repository{root:root, warnings:RefCell::new(vec!())})
+    }else{
+      Err(RepositoryError::NotFound(root))
+    }
+  }
+
+  ///Opens the repository in `start` or the nearest directory above
+  ///it, the way git finds its repository.
+  pub fn discover<P:AsRef<Path>>(start:P) -> RepositoryResult<Repository> {
+    let start = start.as_ref();
+    let mut current = Some(start);
+    while let Some(dir) = current {
+      if let Ok(repo) = Repository::open(dir) {
+        return Ok(repo);
+      }
+      current = dir.parent();
+    }
+    Err(RepositoryError::NotFound(start.to_path_buf()))
+  }
+
+  ///The directory containing the evict directory.
+  pub fn root(&self) -> &Path {
+    self.root.as_path()
+  }
+
+  ///The warnings from saves since the last call, see
+  ///file_manager::save_changes_in.
+  pub fn take_warnings(&self) -> Vec<String> {
+    mem::replace(&mut *self.warnings.borrow_mut(), vec!())
+  }
+
+  pub fn evict_dir(&self) -> PathBuf {
+    self.root.join(file_manager::EVICT_DIRECTORY)
+  }
+
+  pub fn issue_dir(&self) -> PathBuf {
+    self.evict_dir().join(file_manager::ISSUE_DIRECTORY)
+  }
+
+  ///Reads every issue, with its reverse links.
+  pub fn issues(&self) -> Vec<Issue> {
+    let mut issues = file_manager::read_issues_in(self.issue_dir().as_path(),
+                                                  ReadParts::Everything);
+    links::compute_reverse_links(issues.as_mut_slice());
+    issues
+  }
+
+  ///A summary of every issue, from the cache where possible.
+  pub fn summaries(&self) -> Vec<IssueSummary> {
+    issue_cache::read_summaries_in(self.evict_dir().as_path())
+  }
+
+  ///Reads the issues for which `predicate` is true.  Issues are read
+  ///one at a time, and with `ReadParts::BodyOnly` without their events.
+  pub fn query<F:FnMut(&Issue) -> bool>(&self, parts:ReadParts, predicate:F) -> Vec<Issue> {
+    self.reader(parts).filter(predicate).collect()
+  }
+
+  ///Reads the issues one at a time, for callers which can stop early.
+  pub fn reader(&self, parts:ReadParts) -> IssueReader {
+    file_manager::issue_reader_in(self.issue_dir().as_path(), parts)
+  }
+
+  ///Reads the single issue matching `idPart`, resolved as the evict
+  ///commands resolve IDs (see selection::resolve_id).  The cache is
+  ///used to find it and its reverse links, so only that issue is read.
+  pub fn find(&self, idPart:&str) -> RepositoryResult<Issue> {
+    let summaries = self.summaries();
+    let id = try!(selection::resolve_id(idPart, summaries.as_slice()));
+    self.read_with_links(id.as_str(), summaries.as_slice())
+  }
+
+  ///Like find, but doesn't redirect duplicates to the issue they
+  ///duplicate.
+  pub fn find_direct(&self, idPart:&str) -> RepositoryResult<Issue> {
+    let summaries = self.summaries();
+    let id = try!(selection::resolve_id_direct(idPart, summaries.as_slice()));
+    self.read_with_links(id.as_str(), summaries.as_slice())
+  }
+
+  fn read_with_links(&self, id:&str, summaries:&[IssueSummary]) -> RepositoryResult<Issue> {
+    let mut issue = try!(file_manager::read_issue_from_dir(self.issue_dir().join(id)));
+    issue.reverse_links = issue_cache::reverse_links(issue.id(), summaries);
+    Ok(issue)
+  }
+
+  ///Creates and saves a new issue.
+  pub fn create_issue(&self, title:String, body:String, author:Author) -> RepositoryResult<Issue> {
+    self.transaction(|tx| Ok(tx.create(Issue::new(title, body, author)).clone()))
+  }
+
+  ///Adds `event` to the issue matching `idPart`, returning
+  ///the updated issue.
+  pub fn append_event(&self, idPart:&str, event:IssueTimelineEvent) -> RepositoryResult<Issue> {
+    self.transaction(|tx| {
+      tx.update(idPart, |mut issue| {
+        issue.events.push(event);
+        issue
+      }).map(|issue| issue.clone())
+    })
+  }
+
+  ///Saves changes to issues read from this repository.  If another
+  ///process changed one of them since it was read, nothing is saved.
+  pub fn save(&self, changes:&[IssueChange]) -> RepositoryResult<()> {
+    let refs:Vec<&IssueChange> = changes.iter().collect();
+    self.save_refs(refs.as_slice())
+  }
+
+  fn save_refs(&self, changes:&[&IssueChange]) -> RepositoryResult<()> {
+    let warnings = try!(file_manager::save_changes_in(changes, self.issue_dir().as_path()));
+    self.warnings.borrow_mut().extend(warnings);
+    Ok(())
+  }
+
+  ///Runs `body` with the repository locked.  The changes it makes
+  ///through the Transaction are saved if it returns Ok, and dropped
+  ///if it returns an error.
+  pub fn transaction<T, F>(&self, body:F) -> RepositoryResult<T>
+      where F:FnOnce(&mut Transaction) -> RepositoryResult<T> {
+    let _lock = try!(RepoLock::acquire_in(self.evict_dir().as_path())
+                              .map_err(RepositoryError::Lock));
+    let mut tx = Transaction{repo:self, changes:vec!()};
+    let result = try!(body(&mut tx));
+    let changes:Vec<&IssueChange> = tx.changes.iter().collect();
+    try!(self.save_refs(changes.as_slice()));
+    Ok(result)
+  }
+}
+
+///Changes staged while a Repository is locked.  See
+///Repository::transaction.
+pub struct Transaction<'a>{
+  repo:&'a Repository,
+  changes:Vec<IssueChange>
+}
+
+impl<'a> Transaction<'a>{
+  pub fn repository(&self) -> &Repository {
+    self.repo
+  }
+
+  ///Like Repository::find, but sees the changes staged so far.
+  pub fn find(&self, idPart:&str) -> RepositoryResult<Issue> {
+    let found = try!(self.repo.find(idPart));
+    Ok(match self.changes.iter().find(|c| c.after.id() == found.id()) {
+      Some(staged) => staged.after.clone(),
+      None => found
+    })
+  }
+
+  ///Stages a new issue.
+  pub fn create(&mut self, issue:Issue) -> &Issue {
+    self.changes.push(IssueChange::created(issue));
+    &self.changes[self.changes.len() - 1].after
+  }
+
+  ///Stages `update` applied to the issue matching `idPart`.
+  pub fn update<F:FnOnce(Issue) -> Issue>(&mut self, idPart:&str, update:F)
+      -> RepositoryResult<&Issue> {
+    let current = try!(self.find(idPart));
+    let staged = self.changes.iter().position(|c| c.after.id() == current.id());
+    let index = match staged {
+      Some(index) => {
+        self.changes[index].after = update(current);
+        index
+      }
+      None => {
+        self.changes.push(IssueChange::apply(current, update));
+        self.changes.len() - 1
+      }
+    };
+    Ok(&self.changes[index].after)
+  }
+}
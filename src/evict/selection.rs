@@ -17,33 +17,142 @@
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
 use issue::Issue;
+use issue_cache::IssueSummary;
+use repository::{RepositoryError, RepositoryResult};
 
+///What's needed to follow `duplicates` links, so issues and their
+///cached summaries are resolved the same way.
+pub trait Duplicates {
+  fn issue_id(&self) -> &str;
+  ///The ID of the issue this one is marked as a duplicate of.
+  fn duplicate_of(&self) -> Option<String>;
+}
+
+impl Duplicates for Issue {
+  fn issue_id(&self) -> &str {
+    self.id()
+  }
+
+  fn duplicate_of(&self) -> Option<String> {
+    self.links().into_iter()
+        .find(|&(ref link_type, _)| link_type.as_str() == "duplicates")
+        .map(|(_, target)| target)
+  }
+}
+
+impl Duplicates for IssueSummary {
+  fn issue_id(&self) -> &str {
+    self.id.as_str()
+  }
+
+  fn duplicate_of(&self) -> Option<String> {
+    self.links.iter()
+        .find(|&&(ref link_type, _)| link_type.as_str() == "duplicates")
+        .map(|&(_, ref target)| target.clone())
+  }
+}
+
+///Finds issues with an id starting or ending with idPart.  Issues
+///which have been marked as duplicates are replaced by the issue they
+///duplicate, see resolve_duplicate.
 pub fn find_matching_issues(idPart:&str, searchIn:&[Issue]) -> Vec<Issue> {
-  let mut matching:Vec<Issue> = vec!();
-  for issue in searchIn.iter() {
-    if issue.id().ends_with(idPart)
-       || issue.id().starts_with(idPart) {
-      matching.push(issue.clone());
+  find_matching(idPart, searchIn)
+}
+
+///Like find_matching_issues, but searches the cached summaries
+///so issues don't have to be read to resolve an ID.  An issue whose
+///short ID is `idPart` is the only match, even if other IDs start
+///with it.
+pub fn find_matching_summaries(idPart:&str, searchIn:&[IssueSummary]) -> Vec<IssueSummary> {
+  match searchIn.iter().find(|summary| summary.short_id == idPart) {
+    Some(summary) => vec!(resolve_duplicate(summary, searchIn).clone()),
+    None => find_matching(idPart, searchIn)
+  }
+}
+
+fn find_matching<T:Duplicates + Clone + PartialEq>(idPart:&str, searchIn:&[T]) -> Vec<T> {
+  let mut matching:Vec<T> = vec!();
+  for found in searchIn.iter().filter(|item| id_matches(item.issue_id(), idPart)) {
+    let resolved = resolve_duplicate(found, searchIn);
+    if !matching.contains(resolved) {
+      matching.push(resolved.clone());
     }
   }
   matching
 }
 
-pub fn update_issue<UF:Fn(Issue) -> Issue>(idEndPart:&str, searchIn:Vec<Issue>, update: UF)
-  -> Vec<Issue> {
-  let mut matching  = find_matching_issues(idEndPart, searchIn.as_slice());
-  if matching.len() != 1 {
-    println!("Found 0 or >1 matching issues:");
-    for issue in matching.iter() {
-      println!("{} ({})", issue.id(), issue.title());
+///Whether `id` starts or ends with `idPart`.
+pub fn id_matches(id:&str, idPart:&str) -> bool {
+  id.ends_with(idPart) || id.starts_with(idPart)
+}
+
+///The ID of the single issue matching `idPart`, as every command
+///and Repository::find resolve IDs: the ID starts or ends with
+///`idPart`, and duplicates are replaced by the issue they duplicate.
+pub fn resolve_id(idPart:&str, summaries:&[IssueSummary]) -> RepositoryResult<String> {
+  single_id(idPart, find_matching_summaries(idPart, summaries))
+}
+
+///Like resolve_id, but doesn't redirect duplicates, for commands
+///which act on the duplicate itself.
+pub fn resolve_id_direct(idPart:&str, summaries:&[IssueSummary]) -> RepositoryResult<String> {
+  single_id(idPart, summaries.iter().filter(|s| id_matches(s.id.as_str(), idPart)).cloned().collect())
+}
+
+fn single_id(idPart:&str, mut matching:Vec<IssueSummary>) -> RepositoryResult<String> {
+  match matching.len() {
+    0 => Err(RepositoryError::NoMatch(idPart.to_string())),
+    1 => Ok(matching.pop().unwrap().id),
+    _ => Err(RepositoryError::Ambiguous(idPart.to_string(),
+                                        matching.into_iter().map(|s| s.id).collect()))
+  }
+}
+
+///Follows `duplicates` links from an issue to the issue it duplicates,
+///returning the issue itself if it isn't a duplicate.  A loop of
+///duplicates stops before it comes back round.
+pub fn resolve_duplicate<'a, T:Duplicates>(start:&'a T, searchIn:&'a [T]) -> &'a T {
+  let mut current = start;
+  let mut seen:Vec<String> = vec!();
+  loop {
+    seen.push(current.issue_id().to_string());
+    let canonical = match current.duplicate_of() {
+      Some(ref id) if !seen.contains(id) => searchIn.iter().find(|i| i.issue_id() == id.as_str()),
+      _ => None
+    };
+    match canonical {
+      Some(canonical) => current = canonical,
+      None => return current
     }
-    searchIn
-  }else{
-    let mut filtered:Vec<Issue> = searchIn.into_iter()
-                                          .filter(|x| x.id() != matching[0].id())
-                                          .collect();
-    filtered.push(update(matching.pop().unwrap()));
-    filtered
   }
 }
 
+#[test]
+pub fn resolve_id_matches_either_end_and_follows_duplicates(){
+  use author::Author;
+  use issue_cache::assign_short_ids;
+  let summary = |id:&str, links:Vec<(String, String)>| {
+    let mut summary = IssueSummary::new(&Issue::new("A".to_string(), "B".to_string(),
+                                                    Author::parse("C")));
+    summary.id = id.to_string();
+    summary.links = links;
+    summary
+  };
+  let mut summaries = vec!(summary("1111aaaa", vec!()),
+                           summary("2222aaaa", vec!()),
+                           summary("3333bbbb", vec!(("duplicates".to_string(), "1111aaaa".to_string()))),
+                           summary("bbbb4444", vec!()));
+  assign_short_ids(summaries.as_mut_slice());
+  assert_eq!(resolve_id("2222", summaries.as_slice()).ok(), Some("2222aaaa".to_string()));
+  //"bbbb" also starts bbbb4444, but is the short ID of 3333bbbb
+  assert_eq!(resolve_id("bbbb", summaries.as_slice()).ok(), Some("1111aaaa".to_string()));
+  assert_eq!(resolve_id("4444", summaries.as_slice()).ok(), Some("bbbb4444".to_string()));
+  match resolve_id("aaaa", summaries.as_slice()) {
+    Err(RepositoryError::Ambiguous(_, ids)) => assert_eq!(ids.len(), 2),
+    other => panic!("expected an ambiguous match, got {:?}", other)
+  }
+  match resolve_id("0000", summaries.as_slice()) {
+    Err(RepositoryError::NoMatch(_)) => {}
+    other => panic!("expected no match, got {:?}", other)
+  }
+}
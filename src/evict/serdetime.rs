@@ -2,6 +2,38 @@ use time;
 use serde::{Serialize, Deserialize, Serializer, Deserializer};
 
 pub static TIME_FORMAT:&'static str = "%F %Y at %T";
+///Format for dates entered on the command line, e.g. 2014-05-21
+pub static DATE_FORMAT:&'static str = "%Y-%m-%d";
+
+///Parses a date in DATE_FORMAT, returning None if it isn't valid.
+pub fn parse_date(text:&str) -> Option<time::Tm> {
+  time::strptime(text.trim(), DATE_FORMAT).ok()
+}
+
+///The current time in the form times are read back in.  Stored
+///times are local but carry no UTC offset, so the offset is
+///dropped here too to make the two comparable.
+pub fn stored_now() -> time::Tm {
+  time::Tm{tm_utcoff:0, .. time::now()}
+}
+
+///Describes how long before `now` the time `then` was, e.g.
+///"3 days ago".
+pub fn relative_time(then:&time::Tm, now:&time::Tm) -> String {
+  let seconds = (now.to_timespec() - then.to_timespec()).num_seconds();
+  if seconds < 0 {
+    return "in the future".to_string();
+  }
+  let units = [("year", 365 * 86400), ("month", 30 * 86400), ("week", 7 * 86400),
+               ("day", 86400), ("hour", 3600), ("minute", 60)];
+  for &(name, length) in units.iter() {
+    let count = seconds / length;
+    if count > 0 {
+      return format!("{} {}{} ago", count, name, if count == 1 {""} else {"s"});
+    }
+  }
+  "just now".to_string()
+}
 
 #[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, Debug)]
 pub struct SerdeTime(pub time::Tm);
@@ -22,3 +54,15 @@ impl Deserialize for SerdeTime {
     Ok(SerdeTime(time::strptime(&time_str, TIME_FORMAT).unwrap()))
   }
 }
+
+#[test]
+pub fn relative_time_units(){
+  let now = parse_date("2014-05-21").unwrap();
+  let then = |text:&str| parse_date(text).unwrap();
+  assert_eq!(relative_time(&now, &now), "just now".to_string());
+  assert_eq!(relative_time(&then("2014-05-20"), &now), "1 day ago".to_string());
+  assert_eq!(relative_time(&then("2014-05-18"), &now), "3 days ago".to_string());
+  assert_eq!(relative_time(&then("2014-04-01"), &now), "1 month ago".to_string());
+  assert_eq!(relative_time(&then("2012-01-01"), &now), "2 years ago".to_string());
+  assert_eq!(relative_time(&then("2014-06-01"), &now), "in the future".to_string());
+}
@@ -18,7 +18,11 @@
  */
 use file_util;
 use file_manager;
+use config;
 use issue::IssueStatus;
+use serde_json::Error as SerdeError;
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::io::Error as IoError;
 use std::io::Result as IoResult;
 use std::error::Error;
@@ -32,6 +36,7 @@ pub static DEFAULT_STATUS_NAME:&'static str = "<unknown>";
 #[derive(Debug)]
 pub enum StatusWriteError{
   IoWriteFailure(IoError),
+  ConfigWriteFailure(SerdeError),
   InvalidStatus(String)
 }
 
@@ -46,6 +51,7 @@ impl ::std::error::Error for StatusWriteError {
     use self::StatusWriteError::*;
     match *self {
       IoWriteFailure(_) => "I/O to the file failed",
+      ConfigWriteFailure(_) => "Updating the transitions in the config failed",
       InvalidStatus(ref strval) => strval.as_str()
     }
   }
@@ -54,6 +60,7 @@ impl ::std::error::Error for StatusWriteError {
     use self::StatusWriteError::*;
     match *self {
       IoWriteFailure(ref err) => Some(err),
+      ConfigWriteFailure(ref err) => Some(err),
       _ => None
     }
   }
@@ -65,64 +72,343 @@ impl From<IoError> for StatusWriteError {
   }
 }
 
-#[derive(Clone, PartialEq)]
+///What a status means for the work on an issue.  Open
+///and in-progress issues still need work, closed ones don't.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StatusCategory{
+  Open,
+  InProgress,
+  Closed
+}
+
+impl StatusCategory{
+  pub fn from_str(name:&str) -> Option<StatusCategory> {
+    match name {
+      "open" => Some(StatusCategory::Open),
+      "in-progress" => Some(StatusCategory::InProgress),
+      "closed" => Some(StatusCategory::Closed),
+      _ => None
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match *self {
+      StatusCategory::Open => "open",
+      StatusCategory::InProgress => "in-progress",
+      StatusCategory::Closed => "closed"
+    }
+  }
+
+  pub fn is_closed(&self) -> bool {
+    *self == StatusCategory::Closed
+  }
+}
+
+///A status that issues can be set to.  Status options are
+///identified by name, so equality ignores the category.
+#[derive(Clone, Debug)]
 pub struct StatusOption{
-  pub name:String
+  pub name:String,
+  pub category:StatusCategory
+}
+
+impl PartialEq for StatusOption{
+  fn eq(&self, other:&StatusOption) -> bool {
+    self.name == other.name
+  }
 }
 
 impl StatusOption{
+  ///Creates a status option with a category guessed from its name.
+  ///This is also used for status files from before categories existed.
+  pub fn new(name:String) -> StatusOption {
+    let category = if name.as_str() == "closed" {
+      StatusCategory::Closed
+    }else{
+      StatusCategory::Open
+    };
+    StatusOption{name:name, category:category}
+  }
+
   pub fn make_status(&self) -> IssueStatus {
     IssueStatus::new(self.name.to_string())
   }
+
+  fn parse_line(line:&str) -> StatusOption {
+    let mut parts = line.splitn(2, '\t');
+    let name = parts.next().unwrap_or("").to_string();
+    match parts.next().and_then(StatusCategory::from_str) {
+      Some(category) => StatusOption{name:name, category:category},
+      None => StatusOption::new(name)
+    }
+  }
 }
 
-fn full_status_filename() -> String {
-  format!("{}/{}", file_manager::EVICT_DIRECTORY, STATUS_FILE)
+fn evict_directory() -> &'static Path {
+  Path::new(file_manager::EVICT_DIRECTORY)
 }
 
-fn full_default_status_filename() -> String {
-  format!("{}/{}", file_manager::EVICT_DIRECTORY, DEF_STATUS_FILE)
+fn full_status_filename(evictDir:&Path) -> String {
+  evictDir.join(STATUS_FILE).to_string_lossy().into_owned()
+}
+
+fn full_default_status_filename(evictDir:&Path) -> String {
+  evictDir.join(DEF_STATUS_FILE).to_string_lossy().into_owned()
 }
 
 pub fn read_status_options() -> Vec<StatusOption> {
-  let fullString = file_util::read_string_from_file(full_status_filename().as_str())
+  read_status_options_in(evict_directory())
+}
+
+///Like read_status_options, for the evict directory `evictDir`.
+pub fn read_status_options_in(evictDir:&Path) -> Vec<StatusOption> {
+  let fullString = file_util::read_string_from_file(full_status_filename(evictDir).as_str())
                              .unwrap_or("".to_string());
-  fullString.as_str().lines().map(
-    |x| StatusOption{name:x.to_string()}
-  ).collect()
+  fullString.as_str().lines().map(StatusOption::parse_line).collect()
 }
 
 pub fn write_status_options(statuses:Vec<StatusOption>) -> IoResult<()> {
-  let stringVec:Vec<String> = statuses.into_iter().map(|x| x.name).collect();
+  write_status_options_in(evict_directory(), statuses)
+}
+
+///Like write_status_options, for the evict directory `evictDir`.
+pub fn write_status_options_in(evictDir:&Path, statuses:Vec<StatusOption>) -> IoResult<()> {
+  let stringVec:Vec<String> = statuses.into_iter()
+                                      .map(|x| format!("{}\t{}", x.name, x.category.as_str()))
+                                      .collect();
   let fullString = stringVec.join("\n");
   file_util::write_string_to_file(fullString.as_str(),
-                                  full_status_filename().as_str(),
+                                  full_status_filename(evictDir).as_str(),
                                   true)
 }
 
+///Finds a status option either by its full name or by its
+///index in the status list.
+pub fn find_status_option(statusIdent:&str) -> Option<StatusOption> {
+  find_status_option_in(evict_directory(), statusIdent)
+}
+
+///Like find_status_option, for the evict directory `evictDir`.
+pub fn find_status_option_in(evictDir:&Path, statusIdent:&str) -> Option<StatusOption> {
+  let search = read_status_options_in(evictDir);
+  match usize::from_str_radix(statusIdent, 10) {
+    Ok(index) => search.into_iter().nth(index),
+    _ => search.into_iter().find(|x| x.name.as_str() == statusIdent)
+  }
+}
+
+fn check_new_name(name:&str, options:&[StatusOption]) -> Result<(), StatusWriteError> {
+  use self::StatusWriteError::*;
+  if name.trim().len() == 0 || name.contains('\n') || name.contains('\r') || name.contains('\t') {
+    Err(InvalidStatus(format!("'{}' is not a valid status name", name)))
+  }else if usize::from_str_radix(name, 10).is_ok() {
+    //numeric names would be shadowed by set-status's index lookup
+    Err(InvalidStatus(format!("{} is a number, and can't be used as a status name", name)))
+  }else if options.iter().any(|x| x.name.as_str() == name) {
+    Err(InvalidStatus(format!("{} is already a status", name)))
+  }else{
+    Ok(())
+  }
+}
+
+fn position_of(name:&str, options:&[StatusOption]) -> Result<usize, StatusWriteError> {
+  options.iter().position(|x| x.name.as_str() == name).ok_or(
+    StatusWriteError::InvalidStatus(format!("{} is not a valid status option", name)))
+}
+
+///Adds a new status to the end of the status list.
+pub fn add_status_option(name:&str, category:StatusCategory) -> Result<(), StatusWriteError> {
+  let mut options = read_status_options();
+  try!(check_new_name(name, options.as_slice()));
+  options.push(StatusOption{name:name.to_string(), category:category});
+  write_status_options(options).map_err(Into::into)
+}
+
+///Changes the category of an existing status.
+pub fn set_status_category(name:&str, category:StatusCategory) -> Result<(), StatusWriteError> {
+  let mut options = read_status_options();
+  let pos = try!(position_of(name, options.as_slice()));
+  options[pos].category = category;
+  write_status_options(options).map_err(Into::into)
+}
+
+///Finds the category for a status name.  Statuses which are
+///not in the status list are treated as open.
+pub fn category_of(name:&str, options:&[StatusOption]) -> StatusCategory {
+  options.iter().find(|x| x.name.as_str() == name)
+                .map(|x| x.category)
+                .unwrap_or(StatusCategory::Open)
+}
+
+///Checks whether the transition graph in the repo config allows an
+///issue to move from one status to another.  Statuses which have no
+///entry in the graph can move to any status.
+pub fn transition_allowed(from:&str, to:&str) -> bool {
+  if from == to {
+    return true;
+  }
+  let conf = config::Config::load();
+  match conf.transitions.get(from) {
+    Some(allowed) => allowed.iter().any(|x| x.as_str() == to),
+    None => true
+  }
+}
+
+///Checks that the status `oldName` in `evictDir` can be renamed to
+///`newName`, without changing anything, so commands can check before
+///they update issues.
+pub fn check_rename_in(evictDir:&Path, oldName:&str, newName:&str) -> Result<(), StatusWriteError> {
+  let options = read_status_options_in(evictDir);
+  try!(position_of(oldName, options.as_slice()));
+  check_new_name(newName, options.as_slice())
+}
+
+///Checks that the status `name` in `evictDir` can be removed, without
+///changing anything.  The default status can't be removed.
+pub fn check_remove_in(evictDir:&Path, name:&str) -> Result<(), StatusWriteError> {
+  try!(position_of(name, read_status_options_in(evictDir).as_slice()));
+  if read_default_status_in(evictDir).name.as_str() == name {
+    return Err(StatusWriteError::InvalidStatus(
+      format!("{} is the default status and can't be removed", name)));
+  }
+  Ok(())
+}
+
+///Renames a status in the status list of `evictDir`, keeping its
+///position.  If the status is the default status, the default is
+///renamed as well, and so is every mention of it in the config's
+///transitions.  This does not touch any issues.
+pub fn rename_status_option_in(evictDir:&Path, oldName:&str, newName:&str) -> Result<(), StatusWriteError> {
+  try!(check_rename_in(evictDir, oldName, newName));
+  let mut options = read_status_options_in(evictDir);
+  let pos = try!(position_of(oldName, options.as_slice()));
+  let wasDefault = read_default_status_in(evictDir).name.as_str() == oldName;
+  options[pos].name = newName.to_string();
+  let renamed = options[pos].clone();
+  try!(write_status_options_in(evictDir, options));
+  if wasDefault {
+    try!(write_default_status_in(evictDir, &renamed));
+  }
+  update_transitions(evictDir, |transitions| rename_in_transitions(transitions, oldName, newName))
+}
+
+///Removes a status from the status list of `evictDir`, and from the
+///config's transitions.  The default status can't be removed.  This
+///does not touch any issues.
+pub fn remove_status_option_in(evictDir:&Path, name:&str) -> Result<(), StatusWriteError> {
+  try!(check_remove_in(evictDir, name));
+  let mut options = read_status_options_in(evictDir);
+  let pos = try!(position_of(name, options.as_slice()));
+  options.remove(pos);
+  try!(write_status_options_in(evictDir, options));
+  update_transitions(evictDir, |transitions| remove_from_transitions(transitions, name))
+}
+
+///Applies `change` to the transitions in the config, saving the
+///config only if they changed.
+fn update_transitions<F:FnOnce(&mut BTreeMap<String, Vec<String>>)>(evictDir:&Path, change:F)
+    -> Result<(), StatusWriteError> {
+  let mut conf = config::Config::load_in(evictDir);
+  let before = conf.transitions.clone();
+  change(&mut conf.transitions);
+  if conf.transitions == before {
+    Ok(())
+  }else{
+    conf.save_in(evictDir).map_err(StatusWriteError::ConfigWriteFailure)
+  }
+}
+
+///Renames `oldName` to `newName` wherever it appears in `transitions`,
+///as a status moved from or to.
+fn rename_in_transitions(transitions:&mut BTreeMap<String, Vec<String>>, oldName:&str, newName:&str) {
+  if let Some(allowed) = transitions.remove(oldName) {
+    transitions.insert(newName.to_string(), allowed);
+  }
+  for allowed in transitions.values_mut() {
+    for to in allowed.iter_mut().filter(|to| to.as_str() == oldName) {
+      *to = newName.to_string();
+    }
+  }
+}
+
+///Drops `name` from `transitions`, both its own entry and the entries
+///allowing other statuses to move to it.
+fn remove_from_transitions(transitions:&mut BTreeMap<String, Vec<String>>, name:&str) {
+  transitions.remove(name);
+  for allowed in transitions.values_mut() {
+    allowed.retain(|to| to.as_str() != name);
+  }
+}
+
+///Moves a status to a new index in the status list.  Indices past the
+///end of the list move the status to the end.
+pub fn move_status_option(name:&str, newIndex:usize) -> Result<(), StatusWriteError> {
+  let mut options = read_status_options();
+  let pos = try!(position_of(name, options.as_slice()));
+  let moved = options.remove(pos);
+  let insertAt = if newIndex > options.len() {options.len()} else {newIndex};
+  options.insert(insertAt, moved);
+  write_status_options(options).map_err(Into::into)
+}
+
 pub fn read_default_status() -> StatusOption {
-  let fullFile = file_util::read_string_from_file(full_default_status_filename().as_str())
+  read_default_status_in(evict_directory())
+}
+
+///Like read_default_status, for the evict directory `evictDir`.
+pub fn read_default_status_in(evictDir:&Path) -> StatusOption {
+  let fullFile = file_util::read_string_from_file(full_default_status_filename(evictDir).as_str())
                            .unwrap_or(DEFAULT_STATUS_NAME.to_string());
   let lineVec:Vec<&str> = fullFile.as_str().lines().collect();
   let firstLine = lineVec.as_slice().get(0).unwrap_or(&DEFAULT_STATUS_NAME);
   
-  let statusOption = StatusOption{name:firstLine.to_string()};
-  if !read_status_options().contains(&statusOption) {
-    StatusOption{name:DEFAULT_STATUS_NAME.to_string()}
-  }else{
-    statusOption
+  match read_status_options_in(evictDir).into_iter().find(|x| x.name.as_str() == *firstLine) {
+    Some(statusOption) => statusOption,
+    None => StatusOption::new(DEFAULT_STATUS_NAME.to_string())
   }
 }
 
 pub fn write_default_status(status:&StatusOption) -> Result<(), StatusWriteError> {
+  write_default_status_in(evict_directory(), status)
+}
+
+///Like write_default_status, for the evict directory `evictDir`.
+pub fn write_default_status_in(evictDir:&Path, status:&StatusOption) -> Result<(), StatusWriteError> {
   use self::StatusWriteError::*;
-  let isOption = read_status_options().contains(status);
+  let isOption = read_status_options_in(evictDir).contains(status);
   if !isOption {
     Err(InvalidStatus(format!("{} is not a valid status option", status.name.to_string())))
   }else{
     file_util::write_string_to_file(
       status.name.as_str(),
-      full_default_status_filename().as_str(),
+      full_default_status_filename(evictDir).as_str(),
       true).map_err(Into::into)
   }
 }
+
+#[test]
+pub fn parse_status_lines(){
+  let categorized = StatusOption::parse_line("review\tin-progress");
+  assert_eq!(categorized.name, "review".to_string());
+  assert_eq!(categorized.category, StatusCategory::InProgress);
+
+  //status files from before categories only have names
+  assert_eq!(StatusOption::parse_line("closed").category, StatusCategory::Closed);
+  assert_eq!(StatusOption::parse_line("wontfix").category, StatusCategory::Open);
+}
+
+#[test]
+pub fn transitions_follow_renamed_and_removed_statuses(){
+  let mut transitions:BTreeMap<String, Vec<String>> = BTreeMap::new();
+  transitions.insert("open".to_string(), vec!("review".to_string(), "closed".to_string()));
+  transitions.insert("review".to_string(), vec!("open".to_string(), "closed".to_string()));
+
+  rename_in_transitions(&mut transitions, "review", "in-review");
+  assert_eq!(transitions.get("open"), Some(&vec!("in-review".to_string(), "closed".to_string())));
+  assert_eq!(transitions.get("in-review"), Some(&vec!("open".to_string(), "closed".to_string())));
+  assert!(transitions.get("review").is_none());
+
+  remove_from_transitions(&mut transitions, "in-review");
+  assert_eq!(transitions.get("open"), Some(&vec!("closed".to_string())));
+  assert_eq!(transitions.len(), 1);
+}
@@ -0,0 +1,142 @@
+/*
+ *   Copyright 2013 Brandon Sanderson
+ *
+ *   This file is part of Evict-BT.
+ *
+ *   Evict-BT is free software: you can redistribute it and/or modify
+ *   it under the terms of the GNU General Public License as published by
+ *   the Free Software Foundation, either version 3 of the License, or
+ *   (at your option) any later version.
+ *
+ *   Evict-BT is distributed in the hope that it will be useful,
+ *   but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *   GNU General Public License for more details.
+ *
+ *   You should have received a copy of the GNU General Public License
+ *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
+ */
+use file_util;
+use file_manager;
+
+use std::fs;
+use std::io::Result as IoResult;
+use std::path::PathBuf;
+
+static TEMPLATE_DIRECTORY:&'static str = "templates";
+static TEMPLATE_EXTENSION:&'static str = "md";
+static FRONT_MATTER_FENCE:&'static str = "---";
+static GUIDANCE_MARKER:&'static str = "#>";
+
+///A template for new issues, read from `.evict/templates/<name>.md`.
+///The file may start with front-matter between `---` lines, holding
+///`key: value` lines for the defaults below.  Everything after that
+///is the initial body of the issue.
+pub struct IssueTemplate{
+  pub name:String,
+  pub title_prefix:Option<String>,
+  pub tags:Vec<String>,
+  pub status:Option<String>,
+  pub fields:Vec<(String, String)>,
+  pub body:String
+}
+
+fn template_directory() -> PathBuf {
+  PathBuf::from(file_manager::EVICT_DIRECTORY).join(TEMPLATE_DIRECTORY)
+}
+
+///Returns the names of all templates, sorted.
+pub fn template_names() -> Vec<String> {
+  let mut names:Vec<String> = match fs::read_dir(template_directory()) {
+    Ok(entries) => entries.filter_map(|entry| entry.ok())
+                          .map(|entry| entry.path())
+                          .filter(|path| path.extension().map_or(false, |ext| ext == TEMPLATE_EXTENSION))
+                          .filter_map(|path| path.file_stem()
+                                                 .and_then(|stem| stem.to_str())
+                                                 .map(|stem| stem.to_string()))
+                          .collect(),
+    Err(_) => vec!()
+  };
+  names.sort();
+  names
+}
+
+pub fn load_template(name:&str) -> IoResult<IssueTemplate> {
+  let path = template_directory().join(format!("{}.{}", name, TEMPLATE_EXTENSION));
+  file_util::read_string_from_path(&path).map(|text| parse_template(name, text.as_str()))
+}
+
+pub fn parse_template(name:&str, text:&str) -> IssueTemplate {
+  let mut template = IssueTemplate{name:name.to_string(), title_prefix:None, tags:vec!(),
+                                   status:None, fields:vec!(), body:"".to_string()};
+  let lines:Vec<&str> = text.lines().collect();
+  let mut bodyStart = 0;
+  if lines.first().map_or(false, |first| first.trim() == FRONT_MATTER_FENCE) {
+    match lines.iter().skip(1).position(|line| line.trim() == FRONT_MATTER_FENCE) {
+      Some(end) => {
+        for line in lines[1 .. end + 1].iter() {
+          read_front_matter_line(line, &mut template);
+        }
+        bodyStart = end + 2;
+      }
+      //no closing fence, so treat the whole thing as the body
+      None => {}
+    }
+  }
+  template.body = lines[bodyStart ..].join("\n");
+  template
+}
+
+fn read_front_matter_line(line:&str, template:&mut IssueTemplate) {
+  let (key, value) = match line.find(':') {
+    Some(pos) => (line[.. pos].trim(), line[pos + 1 ..].trim()),
+    None => return
+  };
+  if value.len() == 0 {
+    return;
+  }
+  if key.starts_with("field.") {
+    template.fields.push((key["field.".len() ..].to_string(), value.to_string()));
+  }else{
+    match key {
+      "title-prefix" => template.title_prefix = Some(value.to_string()),
+      "status" => template.status = Some(value.to_string()),
+      "tags" => template.tags = value.split(',')
+                                     .map(|tag| tag.trim().to_string())
+                                     .filter(|tag| tag.len() > 0)
+                                     .collect(),
+      _ => {}
+    }
+  }
+}
+
+///Removes guidance lines, which start with `#>`, from text
+///written starting from a template.  Other lines starting with `#`
+///are Markdown headings, and are kept.
+pub fn strip_guidance(text:&str) -> String {
+  let kept:Vec<&str> = text.lines().filter(|line| !line.starts_with(GUIDANCE_MARKER)).collect();
+  kept.join("\n").trim().to_string()
+}
+
+#[test]
+pub fn parse_template_front_matter(){
+  let template = parse_template("bug", "---\n\
+                                        title-prefix: [bug]\n\
+                                        tags: bug, needs-triage\n\
+                                        status: open\n\
+                                        field.component: ui\n\
+                                        ---\n\
+                                        #> Describe how to reproduce the bug\n\
+                                        Steps:");
+  assert_eq!(template.title_prefix, Some("[bug]".to_string()));
+  assert_eq!(template.tags, vec!("bug".to_string(), "needs-triage".to_string()));
+  assert_eq!(template.status, Some("open".to_string()));
+  assert_eq!(template.fields, vec!(("component".to_string(), "ui".to_string())));
+  assert_eq!(strip_guidance(template.body.as_str()), "Steps:".to_string());
+}
+
+#[test]
+pub fn strip_guidance_keeps_headings(){
+  let text = "#> Say what you expected to happen\n## Expected\nNo crash\n#>Then what happened\n## Actual";
+  assert_eq!(strip_guidance(text), "## Expected\nNo crash\n## Actual".to_string());
+}
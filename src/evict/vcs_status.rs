@@ -17,6 +17,7 @@
  *   along with Evict-BT.  If not, see <http://www.gnu.org/licenses/>.
  */
 use std::process;
+use std::path::Path;
 
 enum VCS{
   Git
@@ -37,6 +38,33 @@ impl VCS {
     }
   }
 
+  fn config_value_cmd_output(&self, key:&str) -> Option<String>{
+    match self {
+      &VCS::Git => {
+        let mut gitcmd = process::Command::new("git");
+        gitcmd.arg("config").arg("--get").arg(key);
+        match gitcmd.output() {
+          Ok(ref out) if out.status.success() => String::from_utf8(out.stdout.clone()).ok(),
+          _ => None
+        }
+      }
+    }
+  }
+
+  fn committed_content_cmd_output(&self, path:&Path) -> Option<String>{
+    match self {
+      &VCS::Git => {
+        //./ makes the path relative to the current directory rather than the repo root
+        let mut gitcmd = process::Command::new("git");
+        gitcmd.arg("show").arg(format!("HEAD:./{}", path.to_string_lossy()));
+        match gitcmd.output() {
+          Ok(ref out) if out.status.success() => String::from_utf8(out.stdout.clone()).ok(),
+          _ => None
+        }
+      }
+    }
+  }
+
   fn current() -> VCS {
     VCS::Git  //TODO actually detect a VCS
   }
@@ -47,6 +75,29 @@ pub fn current_branch() -> Option<String> {
   output.and_then(grab_first_line).map(|x| x.to_string())
 }
 
+///The user name configured for the VCS, e.g. `git config user.name`
+pub fn user_name() -> Option<String> {
+  config_value("user.name")
+}
+
+///The user email configured for the VCS, e.g. `git config user.email`
+pub fn user_email() -> Option<String> {
+  config_value("user.email")
+}
+
+///The content of `path` in the last commit, or None if it isn't
+///committed.
+pub fn committed_content(path:&Path) -> Option<String> {
+  VCS::current().committed_content_cmd_output(path)
+}
+
+fn config_value(key:&str) -> Option<String> {
+  VCS::current().config_value_cmd_output(key)
+                .and_then(grab_first_line)
+                .map(|x| x.trim().to_string())
+                .and_then(|x| if x.len() == 0 {None} else {Some(x)})
+}
+
 fn grab_first_line(grab_from:String) -> Option<String> {
   //'loop' through the lines but just return
   //the first line we get